@@ -5,7 +5,7 @@ main.main:
 
 main.p:
     NOP 0;
-    PUSH_FN std.print;
+    PUSH_FN std.prints;
     PUSH_I32 42;
     FEED 1;
     CALL;
@@ -0,0 +1,112 @@
+// Generated from the builtins in src/stdlib.rs by `purec runtime`, do not
+// edit.
+
+// Runtime errors: a partial builtin applied outside its domain panics, naming
+// itself. A program that panics reports it on stderr and exits with 101.
+export const Panic = class extends Error {
+  constructor(builtin, message) {
+    super(`panic in ${builtin}: ${message}`);
+    this.name = "Panic";
+    this.builtin = builtin;
+  }
+};
+export const panic = (builtin, message) => {
+  throw new Panic(builtin, message);
+};
+
+// run :: Va. (() -> Cmd a) -> a
+export const run = (main) => {
+  try {
+    return main()();
+  } catch (error) {
+    if (!(error instanceof Panic)) {
+      throw error;
+    }
+    console.error(error.message);
+    process.exit(101);
+  }
+};
+
+// Trampoline for --trampoline: a tail call between functions returns a
+// Bounce, which the nearest caller not in tail position runs to completion.
+export const Bounce = class {
+  constructor(next) {
+    this.next = next;
+  }
+};
+export const bounce = (next) => new Bounce(next);
+export const land = (result) => {
+  while (result instanceof Bounce) {
+    result = result.next();
+  }
+  return result;
+};
+
+// Constants are cells that compute their value the first time they are
+// used, and only then.
+export const caf = (compute) => {
+  let evaluated = false;
+  let value;
+  return () => {
+    if (!evaluated) {
+      value = compute();
+      evaluated = true;
+    }
+    return value;
+  };
+};
+
+// id :: Va. a -> a
+export const id = (x) => x;
+
+// iff :: Va. Bool -> a -> a -> a
+export const iff$3 = (cond, x, y) => (cond ? x() : y());
+export const iff = (a) => (b) => (c) => iff$3(a, () => b, () => c);
+
+// add :: I32 -> I32 -> I32
+export const add$2 = (x, y) => (x + y) | 0;
+export const add = (a) => (b) => add$2(a, b);
+
+// sub :: I32 -> I32 -> I32
+export const sub$2 = (x, y) => (x - y) | 0;
+export const sub = (a) => (b) => sub$2(a, b);
+
+// mul :: I32 -> I32 -> I32
+export const mul$2 = (x, y) => Math.imul(x, y);
+export const mul = (a) => (b) => mul$2(a, b);
+
+// div :: I32 -> I32 -> I32
+export const div$2 = (x, y) =>
+  y === 0 ? panic("std.div", "division by zero") : (x / y) | 0;
+export const div = (a) => (b) => div$2(a, b);
+
+// prints :: Str -> Cmd Str
+export const prints = (str) => () => {
+  console.log(str);
+  return str;
+};
+
+// cmd :: Va. a -> Cmd a
+export const cmd = (x) => () => x;
+
+// mapCmd :: Va, b. (a -> b) -> Cmd a -> Cmd b
+export const mapCmd$2 = (f, c) => () => f(c());
+export const mapCmd = (a) => (b) => mapCmd$2(a, b);
+
+// swapCmd :: Va, b. a -> Cmd b -> Cmd a
+export const swapCmd$2 = (def, c) => () => {
+  c();
+  return def;
+};
+export const swapCmd = (a) => (b) => swapCmd$2(a, b);
+
+// thenCmd :: Va, b. Cmd a -> Cmd b -> Cmd b
+export const thenCmd$2 = (ca, cb) => () => {
+  ca();
+  return cb();
+};
+export const thenCmd = (a) => (b) => thenCmd$2(a, b);
+
+// chainCmd :: Va, b. Cmd a -> (a -> Cmd b) -> Cmd b
+export const chainCmd$2 = (ca, f) => () => f(ca())();
+export const chainCmd = (a) => (b) => chainCmd$2(a, b);
@@ -1,48 +1,143 @@
+// Generated from the builtins in src/stdlib.rs by `purec runtime`, do not
+// edit.
+
+// Runtime errors: a partial builtin applied outside its domain panics, naming
+// itself. A program that panics reports it on stderr and exits with 101.
+const Panic = class extends Error {
+  constructor(builtin, message) {
+    super(`panic in ${builtin}: ${message}`);
+    this.name = "Panic";
+    this.builtin = builtin;
+  }
+};
+const panic = (builtin, message) => {
+  throw new Panic(builtin, message);
+};
+
+// run :: Va. (() -> Cmd a) -> a
+const run = (main) => {
+  try {
+    return main()();
+  } catch (error) {
+    if (!(error instanceof Panic)) {
+      throw error;
+    }
+    console.error(error.message);
+    process.exit(101);
+  }
+};
+
+// Trampoline for --trampoline: a tail call between functions returns a
+// Bounce, which the nearest caller not in tail position runs to completion.
+const Bounce = class {
+  constructor(next) {
+    this.next = next;
+  }
+};
+const bounce = (next) => new Bounce(next);
+const land = (result) => {
+  while (result instanceof Bounce) {
+    result = result.next();
+  }
+  return result;
+};
+
+// Constants are cells that compute their value the first time they are
+// used, and only then.
+const caf = (compute) => {
+  let evaluated = false;
+  let value;
+  return () => {
+    if (!evaluated) {
+      value = compute();
+      evaluated = true;
+    }
+    return value;
+  };
+};
+
 // id :: Va. a -> a
-id = (x) => x;
+const id = (x) => x;
+
 // iff :: Va. Bool -> a -> a -> a
-iff = (cond) => (x) => (y) => cond ? x : y;
+const iff$3 = (cond, x, y) => (cond ? x() : y());
+const iff = (a) => (b) => (c) => iff$3(a, () => b, () => c);
 
-// add, sub, mul, div :: I32 -> I32 -> I32
-add = (x) => (y) => x + y;
-sub = (x) => (y) => x - y;
-mul = (x) => (y) => x * y;
-div = (x) => (y) => Math.floor(x / y);
+// add :: I32 -> I32 -> I32
+const add$2 = (x, y) => (x + y) | 0;
+const add = (a) => (b) => add$2(a, b);
+
+// sub :: I32 -> I32 -> I32
+const sub$2 = (x, y) => (x - y) | 0;
+const sub = (a) => (b) => sub$2(a, b);
+
+// mul :: I32 -> I32 -> I32
+const mul$2 = (x, y) => Math.imul(x, y);
+const mul = (a) => (b) => mul$2(a, b);
+
+// div :: I32 -> I32 -> I32
+const div$2 = (x, y) =>
+  y === 0 ? panic("std.div", "division by zero") : (x / y) | 0;
+const div = (a) => (b) => div$2(a, b);
+
+// prints :: Str -> Cmd Str
+const prints = (str) => () => {
+  console.log(str);
+  return str;
+};
 
 // cmd :: Va. a -> Cmd a
-cmd = (x) => () => x;
+const cmd = (x) => () => x;
+
 // mapCmd :: Va, b. (a -> b) -> Cmd a -> Cmd b
-mapCmd = (f) => (c) => () => f(c());
+const mapCmd$2 = (f, c) => () => f(c());
+const mapCmd = (a) => (b) => mapCmd$2(a, b);
+
 // swapCmd :: Va, b. a -> Cmd b -> Cmd a
-swapCmd = (def) => (c) => () => {
+const swapCmd$2 = (def, c) => () => {
   c();
   return def;
 };
+const swapCmd = (a) => (b) => swapCmd$2(a, b);
+
 // thenCmd :: Va, b. Cmd a -> Cmd b -> Cmd b
-thenCmd = (ca) => (cb) => () => {
+const thenCmd$2 = (ca, cb) => () => {
   ca();
   return cb();
 };
-// chainCmd :: Va, b. Cmd a -> (a -> Cmd b) -> Cmd b
-chainCmd = (ca) => (f) => () => f(ca())();
+const thenCmd = (a) => (b) => thenCmd$2(a, b);
 
-// prints :: Str -> Cmd Str
-prints = (str) => () => {
-  console.log(str);
-  return str;
-};
+// chainCmd :: Va, b. Cmd a -> (a -> Cmd b) -> Cmd b
+const chainCmd$2 = (ca, f) => () => f(ca())();
+const chainCmd = (a) => (b) => chainCmd$2(a, b);
 
 module.exports = {
+  Panic,
+  panic,
+  run,
+  Bounce,
+  bounce,
+  land,
+  caf,
   id,
+  iff$3,
   iff,
+  add$2,
   add,
+  sub$2,
   sub,
+  mul$2,
   mul,
+  div$2,
   div,
+  prints,
   cmd,
+  mapCmd$2,
   mapCmd,
+  swapCmd$2,
   swapCmd,
+  thenCmd$2,
   thenCmd,
+  chainCmd$2,
   chainCmd,
-  prints,
 };
@@ -1,65 +1,139 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
 
 use crate::def;
+use crate::stdlib::{self, Builtin, BUILTINS};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Version of the image layout that [`Program::as_vec`] writes, bumped on
+/// every incompatible change to it.
+pub const IMAGE_VERSION: u32 = 1;
 
 pub struct Program {
+    /// Builtins the code refers to, by id.
+    imports: BTreeMap<u32, String>,
     data: Vec<u8>,
     code: Vec<Op>,
 }
 
 impl From<def::Program> for Program {
     fn from(program: def::Program) -> Self {
+        let imports = program
+            .definitions
+            .iter()
+            .flat_map(|def| def.code.iter())
+            .filter_map(|op| match op {
+                def::Op::PUSH_FN(id) => stdlib::builtin(id),
+                _ => None,
+            })
+            .map(|builtin| (builtin.vm.id(), builtin.id()))
+            .collect();
         let code = program
             .definitions
             .clone()
             .into_iter()
-            .map(|def| def.code.into_iter())
-            .flatten()
+            .flat_map(|def| def.code.into_iter())
             .map(|op| Op::map(&program, op))
             .collect();
-        Self::from(code)
+        Self::new(imports, vec![], code)
     }
 }
 
 impl Program {
     pub fn from(code: Vec<Op>) -> Self {
-        Self::new(vec![], code)
+        Self::new(BTreeMap::new(), vec![], code)
     }
 
-    pub fn new(data: Vec<u8>, code: Vec<Op>) -> Self {
-        Self { data, code }
+    pub fn new(
+        imports: BTreeMap<u32, String>,
+        data: Vec<u8>,
+        code: Vec<Op>,
+    ) -> Self {
+        Self {
+            imports,
+            data,
+            code,
+        }
     }
 
+    /// The image: a header with [`IMAGE_VERSION`] and the number of imports,
+    /// as two u32, then the import table, the data and the code. Every
+    /// section is aligned to 64 bits and all numbers are little-endian.
     pub fn as_vec(&self) -> Vec<u8> {
-        let mut v = self.data_vec();
+        let mut v = IMAGE_VERSION.to_le_bytes().to_vec();
+        v.extend_from_slice(&(self.imports.len() as u32).to_le_bytes());
+        v.extend_from_slice(self.imports_vec().as_slice());
+        v.extend_from_slice(self.data_vec().as_slice());
         for op in self.code.iter() {
             v.extend_from_slice(op.as_vec().as_slice());
         }
         v
     }
 
+    /// Each import is the builtin's id and the length of its name, as u32,
+    /// followed by the name, so that a VM can check that the ids mean the
+    /// same builtins to it, and reject or relink the image if they don't.
+    fn imports_vec(&self) -> Vec<u8> {
+        let mut v = vec![];
+        for (id, name) in self.imports.iter() {
+            v.extend_from_slice(&id.to_le_bytes());
+            v.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            v.extend_from_slice(name.as_bytes());
+            v.extend_from_slice(
+                vec![0; aligned(name.len()) - name.len()].as_slice(),
+            );
+        }
+        v
+    }
+
     /// Data is going to be aligned to 64 bits.
     fn data_vec(&self) -> Vec<u8> {
-        let length = self.aligned_data_length();
+        let length = aligned(self.data.len());
         let padding = length - self.data.len();
         let mut v = (length as u64).to_le_bytes().to_vec();
         v.extend_from_slice(self.data.as_slice());
         v.extend_from_slice(vec![0; padding].as_slice());
         v
     }
+}
 
-    fn aligned_data_length(&self) -> usize {
-        let n = self.data.len();
-        let bits = n & 7;
-        if bits == 0 {
-            n
-        } else {
-            n + (8 - bits)
+/// Readable dump of the image, for `purec emit asm`: the header, the import
+/// table, the size of the data and the code with the offset of each op.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version {}", IMAGE_VERSION)?;
+        for (id, name) in self.imports.iter() {
+            writeln!(f, "import {} {}", id, name)?;
+        }
+        writeln!(f, "data {} bytes", self.data.len())?;
+        for (i, op) in self.code.iter().enumerate() {
+            match op {
+                Op::PUSH_FN(id) => match self.imports.get(id) {
+                    Some(name) => {
+                        writeln!(f, "{:04} {} ; {}", i * 8, op, name)?
+                    }
+                    None => writeln!(f, "{:04} {}", i * 8, op)?,
+                },
+                _ => writeln!(f, "{:04} {}", i * 8, op)?,
+            }
         }
+        Ok(())
+    }
+}
+
+/// `n` rounded up to a multiple of 8.
+fn aligned(n: usize) -> usize {
+    let bits = n & 7;
+    if bits == 0 {
+        n
+    } else {
+        n + (8 - bits)
     }
 }
 
+#[derive(Clone, Copy)]
 enum Opcode {
     NOP, // DO NOTHING
 
@@ -77,14 +151,18 @@ enum Opcode {
     FEED,   // FEED N top values into the function beneath
     BRANCH, // BRANCH left or right based on a condition
     RETURN, // Return from the routine
+
+    /* Appended, so that the opcodes above keep their numbers */
+    PUSH_CAF, // Push the value of a constant, evaluating it on first use
 }
 
 impl Opcode {
-    pub fn as_vec(self) -> Vec<u8> {
-        (self as u32).to_le_bytes().to_vec()
+    pub fn as_vec(&self) -> Vec<u8> {
+        (*self as u32).to_le_bytes().to_vec()
     }
 }
 
+#[derive(Debug)]
 pub enum Op {
     NOP,       // DO NOTHING
     ARGC(u32), // Specify argument count for Cmd
@@ -98,6 +176,7 @@ pub enum Op {
     PUSH_CMD(u32),   // Push cmd onto the stack
     PUSH_ARG(u32),   // Push cmd argument (by its index) onto the stack
     DROP(u32),       // Drop top value off of the stack
+    PUSH_CAF(u32),   // Push the value of a constant, evaluating it once
 
     /* Program flow */
     FEED(u32), // FEED N top values into the function beneath
@@ -118,6 +197,7 @@ impl Op {
             def::Op::PUSH_CMD(id) => Op::PUSH_CMD(program.get_id(&id) as u32),
             def::Op::PUSH_ARG(index) => Op::PUSH_ARG(index),
             def::Op::DROP(n) => Op::DROP(n),
+            def::Op::PUSH_CAF(id) => Op::PUSH_CAF(program.get_id(&id) as u32),
             def::Op::FEED(n) => Op::FEED(n),
             def::Op::BRANCH => Op::BRANCH,
             def::Op::RETURN => Op::RETURN,
@@ -144,6 +224,9 @@ impl Op {
                 Self::join(Opcode::PUSH_ARG, &index.to_le_bytes())
             }
             Self::DROP(n) => Self::join(Opcode::DROP, &n.to_le_bytes()),
+            Self::PUSH_CAF(addr) => {
+                Self::join(Opcode::PUSH_CAF, &addr.to_le_bytes())
+            }
             Self::FEED(argc) => Self::join(Opcode::FEED, &argc.to_le_bytes()),
             Self::BRANCH => Self::just(Opcode::BRANCH),
             Self::RETURN => Self::just(Opcode::RETURN),
@@ -160,3 +243,49 @@ impl Op {
         v
     }
 }
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ARGC(n) => write!(f, "ARGC {}", n),
+            Self::PUSH_BOOL(b) => write!(f, "PUSH_BOOL {}", b),
+            Self::PUSH_U8(u) => write!(f, "PUSH_U8 {}", u),
+            Self::PUSH_I32(i) => write!(f, "PUSH_I32 {}", i),
+            Self::PUSH_FN(id) => write!(f, "PUSH_FN {}", id),
+            Self::PUSH_CMD(addr) => write!(f, "PUSH_CMD {}", addr),
+            Self::PUSH_ARG(index) => write!(f, "PUSH_ARG {}", index),
+            Self::DROP(n) => write!(f, "DROP {}", n),
+            Self::PUSH_CAF(addr) => write!(f, "PUSH_CAF {}", addr),
+            Self::FEED(n) => write!(f, "FEED {}", n),
+            Self::NOP | Self::PUSH_UNIT | Self::BRANCH | Self::RETURN => {
+                write!(f, "{:?}", self)
+            }
+        }
+    }
+}
+
+/// Builtins by the id that `PUSH_FN` refers to them with, which the VM
+/// dispatches on, with their arity and the parameters they take unevaluated.
+/// Shipped as asm/builtins.tsv.
+pub fn dispatch_table() -> String {
+    let mut builtins: Vec<&Builtin> = BUILTINS.iter().collect();
+    builtins.sort_by_key(|builtin| builtin.vm.id());
+    let mut table = String::from(
+        "# Generated from the builtins in src/stdlib.rs by \
+         `purec runtime --target vm`, do not edit.\n\
+         id\tname\tarity\tlazy\ttype\n",
+    );
+    for builtin in builtins {
+        let lazy: Vec<String> =
+            builtin.lazy.iter().map(|i| i.to_string()).collect();
+        table.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            builtin.vm.id(),
+            builtin.id(),
+            builtin.arity,
+            lazy.join(","),
+            builtin.ty
+        ));
+    }
+    table
+}
@@ -1,28 +1,31 @@
+use crate::fixity::{self, Fixities, Fixity};
 use crate::stdlib;
 use crate::valid::Valid;
 use crate::{parser, parser::Rule};
 use pest::iterators::{Pair, Pairs};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 
-const ENTRYPOINT: &str = "main";
+pub mod json;
 
+pub const ENTRYPOINT: &str = "main.main";
+
+/// Parameter of the lambda that a right operator section `(+ 1)` desugars
+/// into. It is not a valid Pure name, so it cannot capture user names.
+const SECTION_PARAM: &str = "$0";
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct AST {
     pub declarations: HashMap<String, Declaration>,
+    pub fixities: Fixities,
 }
 
 impl TryFrom<Pairs<'_, Rule>> for AST {
     type Error = String;
 
     fn try_from(pairs: Pairs<Rule>) -> Result<Self, Self::Error> {
-        let declarations = pairs
-            .into_iter()
-            .take_while(parser::is_not_eoi)
-            .map(|pair| Declaration::from(pair.into_inner()))
-            .map(|decl| (decl.id.clone(), decl))
-            .collect();
-
-        Self { declarations }.valid()
+        AST::default().extended(pairs)
         // .map(AST::without_unused_declarations)
     }
 }
@@ -31,24 +34,59 @@ impl Valid for AST {
     type Error = String;
 
     fn validate(&self) -> Result<(), Self::Error> {
-        self.check_entrypoint_present()?;
+        self.check_std_declarations()?;
         self.check_undef_ids()?;
+        self.check_unbound_names()?;
+        self.check_fixities()?;
+        self.check_cyclic_definitions()?;
         Ok(())
     }
 }
 
 impl AST {
+    /// This AST with the fixities and declarations parsed from `pairs` added,
+    /// which replace earlier declarations of the same ids. An operator may be
+    /// given a fixity only once in `pairs`.
+    pub fn extended(&self, pairs: Pairs<Rule>) -> Result<Self, String> {
+        self.declared(pairs)?.valid()
+    }
+
+    /// Same as [`AST::extended`], but leaves validating the result to the
+    /// caller, for tools that work with invalid programs too.
+    pub fn declared(&self, pairs: Pairs<Rule>) -> Result<Self, String> {
+        let (fixities, declarations): (Vec<_>, Vec<_>) = pairs
+            .take_while(parser::is_not_eoi)
+            .partition(parser::is_fixity);
+        let mut ast = self.clone();
+        let mut fixed = HashSet::new();
+        for (op, fixity) in fixities.into_iter().map(Fixity::parse) {
+            if !fixed.insert(op.clone()) {
+                return Err(format!("Fixity of {} is declared twice", op));
+            }
+            ast.fixities.insert(op, fixity);
+        }
+        for pair in declarations {
+            let decl = Declaration::parse(pair, &ast.fixities)?;
+            ast.declarations.insert(decl.id.clone(), decl);
+        }
+        Ok(ast)
+    }
+
     /// Declarations vector returned by this method is ordered such that the
-    /// entrypoint is returned as the first element. There are no guarantees as
-    /// to the ordering of the remaining declarations.
+    /// entrypoint, if present, is returned as the first element. The remaining
+    /// declarations follow sorted by id, so that the compiled output is the
+    /// same on every run.
     pub fn get_declarations(&self) -> Vec<Declaration> {
-        vec![self.declarations.get(ENTRYPOINT).unwrap()]
+        let mut rest: Vec<&Declaration> = self
+            .declarations
+            .values()
+            .filter(|decl| decl.id != ENTRYPOINT)
+            .collect();
+        rest.sort_by(|a, b| a.id.cmp(&b.id));
+        self.declarations
+            .get(ENTRYPOINT)
             .into_iter()
-            .chain(
-                self.declarations
-                    .values()
-                    .filter(|decl| decl.id != ENTRYPOINT),
-            )
+            .chain(rest)
             .cloned()
             .collect()
     }
@@ -61,7 +99,9 @@ impl AST {
     fn without_unused_declarations(mut self) -> Self {
         let declared: HashSet<String> =
             self.declarations.keys().cloned().collect();
-        let referenced = &self.get_ref_ids();
+        let mut referenced = self.get_ref_ids();
+        referenced.insert(ENTRYPOINT.to_string());
+        let referenced = &referenced;
         let unused = declared.difference(referenced);
         for reference in unused {
             self.declarations.remove(reference);
@@ -71,10 +111,17 @@ impl AST {
 
     fn get_ref_ids(&self) -> HashSet<String> {
         self.declarations
-            .iter()
-            .map(|(_, decl)| decl.expr.get_ids())
-            .flatten()
-            .chain(vec![ENTRYPOINT.to_string()].into_iter())
+            .values()
+            .flat_map(|decl| decl.expr.get_ids())
+            .collect()
+    }
+
+    /// Builtins referenced anywhere in the program.
+    pub fn get_std_ids(&self) -> BTreeSet<String> {
+        let index = stdlib::index();
+        self.get_ref_ids()
+            .into_iter()
+            .filter(|id| index.contains_key(id))
             .collect()
     }
 
@@ -102,99 +149,587 @@ impl AST {
         }
     }
 
-    fn check_entrypoint_present(&self) -> Result<(), String> {
+    /// Validates an expression that is not part of any declaration, such as
+    /// one entered in the REPL, against this AST.
+    pub fn check_expr(&self, expr: &Expr) -> Result<(), String> {
+        let mut unknown: Vec<String> = expr
+            .get_ids()
+            .difference(&self.get_known_ids())
+            .cloned()
+            .collect();
+        unknown.sort();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown references found: {}",
+                unknown.join(", ")
+            ));
+        }
+        let mut unbound: Vec<String> =
+            expr.get_free_names().into_iter().collect();
+        unbound.sort();
+        if unbound.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Unbound names found: {}", unbound.join(", ")))
+        }
+    }
+
+    /// Bare names may only refer to lambda parameters, declarations are always
+    /// referenced by their namespaced id.
+    fn check_unbound_names(&self) -> Result<(), String> {
+        let mut unbound: Vec<String> = self
+            .declarations
+            .values()
+            .flat_map(|decl| decl.expr.get_free_names())
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect();
+        unbound.sort();
+        if unbound.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Unbound names found: {}", unbound.join(", ")))
+        }
+    }
+
+    /// The `std` namespace belongs to the builtins, which the backends refer
+    /// to directly, so a program can't declare anything in it.
+    fn check_std_declarations(&self) -> Result<(), String> {
+        let mut std: Vec<&str> = self
+            .declarations
+            .keys()
+            .filter(|id| id.starts_with("std."))
+            .map(String::as_str)
+            .collect();
+        std.sort();
+        if std.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Declarations in the std namespace are reserved for \
+                 builtins: {}",
+                std.join(", ")
+            ))
+        }
+    }
+
+    fn check_fixities(&self) -> Result<(), String> {
+        let mut undeclared: Vec<String> = self
+            .fixities
+            .keys()
+            .filter(|op| !self.declarations.contains_key(*op))
+            .cloned()
+            .collect();
+        undeclared.sort();
+        if undeclared.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Fixity declared for unknown operators: {}",
+                undeclared.join(", ")
+            ))
+        }
+    }
+
+    /// Declarations that aren't functions are evaluated once, when first
+    /// used, so one that needs its own value to get evaluated never gets one.
+    fn check_cyclic_definitions(&self) -> Result<(), String> {
+        let mut ids: Vec<&str> =
+            self.declarations.keys().map(String::as_str).collect();
+        ids.sort();
+        let mut done = HashSet::new();
+        match ids
+            .into_iter()
+            .find_map(|id| self.find_cycle(id, &mut vec![], &mut done))
+        {
+            Some(id) => Err(format!("Cyclic definition of {}", id)),
+            None => Ok(()),
+        }
+    }
+
+    /// The first declaration found to depend on itself when evaluating `id`,
+    /// with `visiting` holding the declarations being evaluated on the way.
+    fn find_cycle<'a>(
+        &'a self,
+        id: &'a str,
+        visiting: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<&'a str> {
+        let decl = self.declarations.get(id)?;
+        if matches!(decl.expr, Expr::Func(..)) || done.contains(id) {
+            return None;
+        }
+        if visiting.contains(&id) {
+            return Some(id);
+        }
+        visiting.push(id);
+        let cycle = self
+            .get_strict_ids(&decl.expr)
+            .into_iter()
+            .find_map(|next| self.find_cycle(next, visiting, done));
+        visiting.pop();
+        done.insert(id);
+        cycle
+    }
+
+    /// Ids whose values evaluating `expr` needs right away, which leaves out
+    /// those under lambdas and in arguments that builtins take unevaluated.
+    fn get_strict_ids<'a>(&'a self, expr: &'a Expr) -> Vec<&'a str> {
+        match expr {
+            Expr::ID(id, _) => vec![id],
+            Expr::Call(..) => {
+                let (f, args) = expr.flatten();
+                let lazy = self.lazy_params(f, args.len());
+                args.into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| !lazy.contains(i))
+                    .flat_map(|(_, arg)| self.get_strict_ids(arg))
+                    .chain(self.get_strict_ids(f))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Parameters that a call of `f` with `argc` arguments leaves unevaluated,
+    /// which are only those of a saturated builtin, possibly under an alias.
+    pub fn lazy_params(&self, f: &Expr, argc: usize) -> &'static [usize] {
+        let Expr::ID(id, _) = f else {
+            return &[];
+        };
+        match stdlib::builtin(self.resolve(id)) {
+            Some(builtin) if argc >= builtin.arity => builtin.lazy,
+            _ => &[],
+        }
+    }
+
+    /// The id that an alias, i.e. a declaration of just another id, stands
+    /// for.
+    pub fn resolve<'a>(&'a self, mut id: &'a str) -> &'a str {
+        for _ in 0..self.declarations.len() {
+            match self.declarations.get(id) {
+                Some(Declaration {
+                    expr: Expr::ID(target, _),
+                    ..
+                }) => id = target,
+                _ => break,
+            }
+        }
+        id
+    }
+
+    /// Programs need an entrypoint, libraries don't, so this is checked
+    /// separately from the rest of the validation.
+    pub fn check_entrypoint_present(&self) -> Result<(), String> {
         match self.declarations.get(ENTRYPOINT) {
             None => Err(format!("Missing entrypoint: {}", ENTRYPOINT)),
             _ => Ok(()),
         }
     }
+
+    pub fn check_declared(&self, ids: &[String]) -> Result<(), String> {
+        let unknown: Vec<&str> = ids
+            .iter()
+            .filter(|id| !self.declarations.contains_key(*id))
+            .map(String::as_str)
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Unknown declarations: {}", unknown.join(", ")))
+        }
+    }
+}
+
+/// Location of a piece of source code. Lines and columns start at 1, columns
+/// count characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span) -> Self {
+        Self {
+            start: span.start_pos().into(),
+            end: span.end_pos().into(),
+        }
+    }
+}
+
+impl From<pest::Position<'_>> for Position {
+    fn from(pos: pest::Position) -> Self {
+        let (line, column) = pos.line_col();
+        Self {
+            offset: pos.pos(),
+            line,
+            column,
+        }
+    }
+}
+
+/// A `{- comment -}`. The parser skips them, so they are not part of the
+/// declarations, but tools that work with the source need them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    /// The comment with its delimiters.
+    pub text: String,
+    pub span: Span,
+}
+
+/// The comments in `source`, in order.
+pub fn comments(source: &str) -> Vec<Comment> {
+    let mut comments = vec![];
+    let mut from = 0;
+    while let Some(start) = source[from..].find("{-").map(|i| from + i) {
+        let Some(end) = source[start + 2..].find("-}") else {
+            break;
+        };
+        let end = start + 2 + end + 2;
+        comments.push(Comment {
+            text: source[start..end].to_string(),
+            span: pest::Span::new(source, start, end).unwrap().into(),
+        });
+        from = end;
+    }
+    comments
+}
+
+/// Printed as `line:column-line:column`.
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+impl Span {
+    pub fn to(self, other: Self) -> Self {
+        Self {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+/// Readable dump of the AST, for `purec emit ast`: the fixities, then the
+/// declarations by id, with one node of their expressions per line, indented
+/// under its parent.
+impl fmt::Display for AST {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fixities: Vec<_> = self.fixities.iter().collect();
+        fixities.sort_by_key(|(op, _)| *op);
+        for (op, fixity) in fixities {
+            writeln!(f, "{} {} {}", fixity.assoc, fixity.precedence, op)?;
+        }
+        let mut ids: Vec<&String> = self.declarations.keys().collect();
+        ids.sort();
+        for id in ids {
+            let decl = &self.declarations[id];
+            writeln!(f, "{} {}", decl.id, decl.span)?;
+            decl.expr.dump(f, 1)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Declaration {
     pub id: String,
     pub expr: Expr,
+    pub span: Span,
 }
 
-impl From<Pairs<'_, Rule>> for Declaration {
-    fn from(pairs: Pairs<Rule>) -> Self {
-        let mut it = pairs.into_iter();
-        let id = Expr::string(it.next().unwrap());
-        let expr = it.next().unwrap().into();
-        Self { id, expr }
+impl Declaration {
+    fn parse(pair: Pair<Rule>, fixities: &Fixities) -> Result<Self, String> {
+        let span = pair.as_span().into();
+        let mut pairs = pair.into_inner();
+        let id = pairs.next().unwrap();
+        let id = match id.as_rule() {
+            Rule::operator => Expr::operator(id),
+            _ => Expr::string(id),
+        };
+        let expr = Expr::parse(pairs.next().unwrap(), fixities)?;
+        Ok(Self { id, expr, span })
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
-    Int(i32),                          // -42
-    Name(String),                      // x
-    ID(String),                        // main.example
-    Call(Box<Self>, Box<Vec<Self>>),   // f a main.b 42 (std.print 58)
-    Func(Box<Vec<String>>, Box<Self>), // a -> b -> Expr
+    Int(i32, Span),                     // -42
+    Name(String, Span),                 // x
+    ID(String, Span),                   // main.example
+    Call(Box<Self>, Vec<Self>, Span),   // f a main.b 42 (std.prints 58)
+    Func(Vec<String>, Box<Self>, Span), // a -> b -> Expr
 }
 
-impl From<Pair<'_, Rule>> for Expr {
-    fn from(pair: Pair<Rule>) -> Self {
+impl Expr {
+    pub fn parse(
+        pair: Pair<Rule>,
+        fixities: &Fixities,
+    ) -> Result<Self, String> {
+        let span = pair.as_span().into();
         match pair.as_rule() {
             Rule::int => Self::int(pair),
-            Rule::name => Self::name(pair),
-            Rule::id => Self::id(pair),
-            Rule::call => Self::call(pair.into_inner().into()),
-            Rule::func => Self::func(pair.into_inner().into()),
+            Rule::name => Ok(Self::name(pair)),
+            Rule::id => Ok(Self::id(pair)),
+            Rule::operator => Ok(Self::ID(Self::operator(pair), span)),
+            Rule::call => Self::call(pair.into_inner(), fixities, span),
+            Rule::func => Self::func(pair.into_inner(), fixities, span),
+            Rule::binop => Self::binop(pair.into_inner(), fixities),
+            Rule::do_block => Self::do_block(pair.into_inner(), fixities),
+            Rule::left_section => {
+                Self::left_section(pair.into_inner(), fixities, span)
+            }
+            Rule::right_section => {
+                Self::right_section(pair.into_inner(), fixities, span)
+            }
             _ => unreachable!("Expr from Pair"),
         }
     }
-}
 
-impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Int(_, span)
+            | Self::Name(_, span)
+            | Self::ID(_, span)
+            | Self::Call(_, _, span)
+            | Self::Func(_, _, span) => *span,
+        }
+    }
+
+    fn dump(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self {
+            Self::Int(i, span) => writeln!(f, "{}Int {} {}", indent, i, span),
+            Self::Name(name, span) => {
+                writeln!(f, "{}Name {} {}", indent, name, span)
+            }
+            Self::ID(id, span) => writeln!(f, "{}ID {} {}", indent, id, span),
+            Self::Call(func, args, span) => {
+                writeln!(f, "{}Call {}", indent, span)?;
+                func.dump(f, depth + 1)?;
+                args.iter().try_for_each(|arg| arg.dump(f, depth + 1))
+            }
+            Self::Func(params, body, span) => {
+                writeln!(f, "{}Func {} {}", indent, params.join(" "), span)?;
+                body.dump(f, depth + 1)
+            }
+        }
+    }
+
+    fn get_free_names(&self) -> HashSet<String> {
+        match self {
+            Self::Name(name, _) => HashSet::from([name.clone()]),
+            Self::Call(f, args, _) => f
+                .get_free_names()
+                .into_iter()
+                .chain(args.iter().flat_map(|arg| arg.get_free_names()))
+                .collect(),
+            Self::Func(params, expr, _) => expr
+                .get_free_names()
+                .into_iter()
+                .filter(|name| !params.contains(name))
+                .collect(),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// The function of a call and all the arguments applied to it, so that
+    /// `(f a) b` is treated as `f a b`, as the JS backend does.
+    pub fn flatten(&self) -> (&Expr, Vec<&Expr>) {
+        let mut f = self;
+        let mut groups = vec![];
+        while let Self::Call(g, args, _) = f {
+            groups.push(args);
+            f = g;
+        }
+        (f, groups.into_iter().rev().flatten().collect())
+    }
+
     fn get_ids(&self) -> HashSet<String> {
         match self {
-            Self::ID(id) => HashSet::from([id.clone()]),
-            Self::Call(f, args) => f
+            Self::ID(id, _) => HashSet::from([id.clone()]),
+            Self::Call(f, args, _) => f
                 .get_ids()
                 .into_iter()
-                .chain(args.iter().map(|arg| arg.get_ids()).flatten())
+                .chain(args.iter().flat_map(|arg| arg.get_ids()))
                 .collect(),
-            Self::Func(_, expr) => expr.get_ids(),
+            Self::Func(_, expr, _) => expr.get_ids(),
             _ => HashSet::new(),
         }
     }
 
-    pub fn int(pair: Pair<Rule>) -> Self {
-        Self::Int(pair.as_str().parse().unwrap())
+    /// Integers are 32 bits wide, so larger literals are rejected rather
+    /// than wrapped around.
+    pub fn int(pair: Pair<Rule>) -> Result<Self, String> {
+        let span: Span = pair.as_span().into();
+        pair.as_str()
+            .parse()
+            .map(|i| Self::Int(i, span))
+            .map_err(|_| {
+                format!(
+                    "Integer literal {} at {}:{} does not fit in 32 bits",
+                    pair.as_str(),
+                    span.start.line,
+                    span.start.column
+                )
+            })
     }
 
     pub fn name(pair: Pair<Rule>) -> Self {
-        Self::Name(Self::string(pair))
+        let span = pair.as_span().into();
+        Self::Name(Self::string(pair), span)
     }
 
     pub fn id(pair: Pair<Rule>) -> Self {
-        Self::ID(Self::string(pair))
+        let span = pair.as_span().into();
+        Self::ID(Self::string(pair), span)
     }
 
-    pub fn call(pairs: Pairs<Rule>) -> Self {
-        let mut it = pairs.into_iter();
-        let f = it.next().unwrap().into();
-        let args = it.map(|pair| pair.into()).collect();
-        Self::Call(Box::new(f), Box::new(args))
+    pub fn call(
+        mut pairs: Pairs<Rule>,
+        fixities: &Fixities,
+        span: Span,
+    ) -> Result<Self, String> {
+        let f = Self::parse(pairs.next().unwrap(), fixities)?;
+        let args = pairs
+            .map(|pair| Self::parse(pair, fixities))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::Call(Box::new(f), args, span))
     }
 
-    pub fn func(pairs: Pairs<Rule>) -> Self {
+    pub fn func(
+        pairs: Pairs<Rule>,
+        fixities: &Fixities,
+        span: Span,
+    ) -> Result<Self, String> {
         let params = pairs
             .clone()
-            .into_iter()
             .take_while(parser::is_param)
             .map(Expr::param)
             .collect();
         let expr = pairs
             .clone()
+            .find(|pair| !parser::is_param(pair))
+            .map(|expr| Self::parse(expr, fixities))
+            .unwrap()?;
+        Ok(Expr::Func(params, Box::new(expr), span))
+    }
+
+    pub fn binop(
+        pairs: Pairs<Rule>,
+        fixities: &Fixities,
+    ) -> Result<Self, String> {
+        let (ops, operands): (Vec<_>, Vec<_>) =
+            pairs.partition(|pair| matches!(pair.as_rule(), Rule::ops));
+        let operands = operands
             .into_iter()
-            .filter(|pair| !parser::is_param(pair))
-            .next()
-            .map(|expr| expr.into())
-            .unwrap();
-        Expr::Func(Box::new(params), Box::new(expr))
+            .map(|pair| Self::parse(pair, fixities))
+            .collect::<Result<_, _>>()?;
+        let ops = ops.into_iter().map(Self::id).collect();
+        fixity::resolve(fixities, operands, ops)
+    }
+
+    /// `do { x <- a; b; pure x }` is sequenced with the Cmd combinators:
+    /// `std.chainCmd a (x -> std.thenCmd b (std.cmd x))`.
+    pub fn do_block(
+        pairs: Pairs<Rule>,
+        fixities: &Fixities,
+    ) -> Result<Self, String> {
+        let mut statements = pairs
+            .filter(|pair| !matches!(pair.as_rule(), Rule::do_kw))
+            .rev();
+        let last = statements.next().unwrap();
+        if let Rule::bind = last.as_rule() {
+            let (line, column) = last.line_col();
+            return Err(format!(
+                "The do block ends in a binding at {}:{}, it must end in a \
+                 command",
+                line, column
+            ));
+        }
+        let mut expr = Self::statement(last, fixities)?;
+        for pair in statements {
+            let span = Span::from(pair.as_span()).to(expr.span());
+            expr = match pair.as_rule() {
+                Rule::bind => {
+                    let mut pairs = pair.into_inner();
+                    let name = Self::string(pairs.next().unwrap());
+                    let cmd = Self::parse(pairs.next().unwrap(), fixities)?;
+                    let rest = Self::Func(vec![name], expr.into(), span);
+                    Self::builtin("std.chainCmd", vec![cmd, rest], span)
+                }
+                _ => {
+                    let cmd = Self::statement(pair, fixities)?;
+                    Self::builtin("std.thenCmd", vec![cmd, expr], span)
+                }
+            };
+        }
+        Ok(expr)
+    }
+
+    /// A statement of a do block that doesn't bind a name.
+    fn statement(
+        pair: Pair<Rule>,
+        fixities: &Fixities,
+    ) -> Result<Self, String> {
+        match pair.as_rule() {
+            Rule::pure => {
+                let span = pair.as_span().into();
+                let value = pair.into_inner().nth(1).unwrap();
+                let value = Self::parse(value, fixities)?;
+                Ok(Self::builtin("std.cmd", vec![value], span))
+            }
+            _ => Self::parse(pair, fixities),
+        }
+    }
+
+    fn builtin(id: &str, args: Vec<Self>, span: Span) -> Self {
+        Self::Call(Self::ID(id.to_string(), span).into(), args, span)
+    }
+
+    /// `(x +)` is the partial application `(+) x`.
+    pub fn left_section(
+        mut pairs: Pairs<Rule>,
+        fixities: &Fixities,
+        span: Span,
+    ) -> Result<Self, String> {
+        let operand = Self::parse(pairs.next().unwrap(), fixities)?;
+        let op = Self::id(pairs.next().unwrap());
+        Ok(Self::Call(Box::new(op), vec![operand], span))
+    }
+
+    /// `(+ 1)` is the lambda `x -> x + 1`.
+    pub fn right_section(
+        mut pairs: Pairs<Rule>,
+        fixities: &Fixities,
+        span: Span,
+    ) -> Result<Self, String> {
+        let op = Self::id(pairs.next().unwrap());
+        let operand = Self::parse(pairs.next().unwrap(), fixities)?;
+        let param = Self::Name(SECTION_PARAM.to_string(), op.span());
+        Ok(Self::Func(
+            vec![SECTION_PARAM.to_string()],
+            Box::new(Self::Call(Box::new(op), vec![param, operand], span)),
+            span,
+        ))
+    }
+
+    /// Operator name of the `(+)` pair.
+    pub fn operator(pair: Pair<Rule>) -> String {
+        Self::string(pair.into_inner().next().unwrap())
     }
 
     pub fn param(pair: Pair<Rule>) -> String {
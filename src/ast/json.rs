@@ -0,0 +1,319 @@
+//! The AST as JSON, for tools that work with programs without linking to the
+//! compiler. `purec emit ast-json` prints it, and the commands that compile
+//! programs read it back, so tools can also generate programs.
+//!
+//! The schema is versioned by [`VERSION`], which is bumped on every change
+//! that could break a reader. Within a version, keys are only ever added.
+//!
+//! ```text
+//! AST         { version: 1, fixities: [Fixity], declarations: [Declaration],
+//!               comments: [Comment] }
+//! Fixity      { operator: "+", assoc: "infixl" | "infixr" | "infix",
+//!               precedence: 0..9 }
+//! Declaration { id: "main.main" | "+", expr: Expr, span: Span }
+//! Comment     { text: "{- with its delimiters -}", span: Span }
+//! Expr        { kind: "Int", value: i32, span: Span }
+//!           | { kind: "Name", name: "x" | "$0", span: Span }
+//!           | { kind: "ID", id: "std.add" | "+", span: Span }
+//!           | { kind: "Call", func: Expr, args: [Expr], span: Span }
+//!           | { kind: "Func", params: ["x" | "$0"], body: Expr, span: Span }
+//! Span        { start: Position, end: Position }
+//! Position    { offset: 0, line: 1, column: 1 }
+//! ```
+//!
+//! Operators are desugared as the parser does: `a + b` is a `Call` of the
+//! `ID` `+`, and a right section `(+ 1)` a `Func` of the reserved name `$0`.
+//! Fixities are sorted by operator and declarations by id, so that the same
+//! program always gives the same JSON. Spans may be left out of programs
+//! that have no source, and comments always may.
+
+use super::{Comment, Declaration, Expr, Position, Span, AST, SECTION_PARAM};
+use crate::fixity::{Assoc, Fixity};
+use crate::parser::{PureParser, Rule};
+use crate::valid::Valid;
+use pest::Parser;
+use serde_json::{json, Value};
+
+pub const VERSION: u64 = 1;
+
+pub fn to_json(ast: &AST, comments: &[Comment]) -> Value {
+    let mut fixities: Vec<_> = ast.fixities.iter().collect();
+    fixities.sort_by_key(|(op, _)| *op);
+    let mut declarations: Vec<&Declaration> =
+        ast.declarations.values().collect();
+    declarations.sort_by_key(|decl| &decl.id);
+    json!({
+        "version": VERSION,
+        "fixities": fixities
+            .into_iter()
+            .map(|(op, fixity)| json!({
+                "operator": op,
+                "assoc": fixity.assoc.to_string(),
+                "precedence": fixity.precedence,
+            }))
+            .collect::<Vec<_>>(),
+        "declarations": declarations
+            .into_iter()
+            .map(|decl| json!({
+                "id": decl.id,
+                "expr": expr(&decl.expr),
+                "span": span(decl.span),
+            }))
+            .collect::<Vec<_>>(),
+        "comments": comments
+            .iter()
+            .map(|comment| json!({
+                "text": comment.text,
+                "span": span(comment.span),
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn expr(expr: &Expr) -> Value {
+    match expr {
+        Expr::Int(i, s) => json!({"kind": "Int", "value": i, "span": span(*s)}),
+        Expr::Name(name, s) => {
+            json!({"kind": "Name", "name": name, "span": span(*s)})
+        }
+        Expr::ID(id, s) => json!({"kind": "ID", "id": id, "span": span(*s)}),
+        Expr::Call(f, args, s) => json!({
+            "kind": "Call",
+            "func": self::expr(f),
+            "args": args.iter().map(self::expr).collect::<Vec<_>>(),
+            "span": span(*s),
+        }),
+        Expr::Func(params, body, s) => json!({
+            "kind": "Func",
+            "params": params,
+            "body": self::expr(body),
+            "span": span(*s),
+        }),
+    }
+}
+
+fn span(span: Span) -> Value {
+    json!({"start": position(span.start), "end": position(span.end)})
+}
+
+fn position(position: Position) -> Value {
+    json!({
+        "offset": position.offset,
+        "line": position.line,
+        "column": position.column,
+    })
+}
+
+/// Reads the AST back, checking that it is one that the parser could have
+/// produced, and validates it as the parser does. Errors say where in the
+/// JSON they are, e.g. `declarations[0].expr.args[1]`.
+pub fn from_json(json: &Value) -> Result<(AST, Vec<Comment>), String> {
+    match json.get("version").and_then(Value::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+                "Unsupported AST JSON version {}, expected {}",
+                version, VERSION
+            ))
+        }
+        None => return Err("AST JSON without a version".to_string()),
+    }
+    let mut ast = AST::default();
+    for (i, fixity) in array(json, "", "fixities")?.iter().enumerate() {
+        let path = format!("fixities[{}]", i);
+        let op = string(fixity, &path, "operator")?;
+        if !is(Rule::ops, &op) {
+            return Err(format!("{}: invalid operator {:?}", path, op));
+        }
+        let assoc = match string(fixity, &path, "assoc")?.as_str() {
+            "infixl" => Assoc::Left,
+            "infixr" => Assoc::Right,
+            "infix" => Assoc::None,
+            assoc => {
+                return Err(format!("{}: invalid assoc {:?}", path, assoc))
+            }
+        };
+        let precedence = match field(fixity, &path, "precedence")?.as_u64() {
+            Some(precedence @ 0..=9) => precedence as u8,
+            _ => return Err(format!("{}.precedence: expected 0 to 9", path)),
+        };
+        if ast.fixities.contains_key(&op) {
+            return Err(format!(
+                "{}: fixity of {} is declared twice",
+                path, op
+            ));
+        }
+        ast.fixities.insert(op, Fixity { assoc, precedence });
+    }
+    let declarations = array(json, "", "declarations")?;
+    for (i, decl) in declarations.iter().enumerate() {
+        let path = format!("declarations[{}]", i);
+        let id = string(decl, &path, "id")?;
+        if !is(Rule::id, &id) && !is(Rule::ops, &id) {
+            return Err(format!("{}: invalid id {:?}", path, id));
+        }
+        if ast.declarations.contains_key(&id) {
+            return Err(format!("{}: {} is declared twice", path, id));
+        }
+        let declaration = Declaration {
+            expr: from_expr(
+                field(decl, &path, "expr")?,
+                format!("{}.expr", path),
+            )?,
+            span: from_span(decl, &path)?,
+            id: id.clone(),
+        };
+        ast.declarations.insert(id, declaration);
+    }
+    let comments = match json.get("comments") {
+        None => vec![],
+        Some(_) => array(json, "", "comments")?
+            .iter()
+            .enumerate()
+            .map(|(i, comment)| {
+                let path = format!("comments[{}]", i);
+                Ok(Comment {
+                    text: string(comment, &path, "text")?,
+                    span: from_span(comment, &path)?,
+                })
+            })
+            .collect::<Result<_, String>>()?,
+    };
+    Ok((ast.valid()?, comments))
+}
+
+fn from_expr(expr: &Value, path: String) -> Result<Expr, String> {
+    let span = from_span(expr, &path)?;
+    match string(expr, &path, "kind")?.as_str() {
+        "Int" => match field(expr, &path, "value")?.as_i64() {
+            Some(i) => {
+                i32::try_from(i).map(|i| Expr::Int(i, span)).map_err(|_| {
+                    format!("{}: {} does not fit in 32 bits", path, i)
+                })
+            }
+            None => Err(format!("{}.value: expected an integer", path)),
+        },
+        "Name" => Ok(Expr::Name(name(expr, &path, "name")?, span)),
+        "ID" => {
+            let id = string(expr, &path, "id")?;
+            if !is(Rule::id, &id) && !is(Rule::ops, &id) {
+                return Err(format!("{}: invalid id {:?}", path, id));
+            }
+            Ok(Expr::ID(id, span))
+        }
+        "Call" => {
+            let f = from_expr(
+                field(expr, &path, "func")?,
+                format!("{}.func", path),
+            )?;
+            let args = array(expr, &path, "args")?;
+            if args.is_empty() {
+                return Err(format!("{}.args: expected at least one", path));
+            }
+            let args = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| from_expr(arg, format!("{}.args[{}]", path, i)))
+                .collect::<Result<_, _>>()?;
+            Ok(Expr::Call(f.into(), args, span))
+        }
+        "Func" => {
+            let params = array(expr, &path, "params")?;
+            if params.is_empty() {
+                return Err(format!("{}.params: expected at least one", path));
+            }
+            let params = params
+                .iter()
+                .map(|param| match param.as_str() {
+                    Some(param) if is_name(param) => Ok(param.to_string()),
+                    _ => Err(format!("{}.params: invalid {}", path, param)),
+                })
+                .collect::<Result<_, _>>()?;
+            let body = from_expr(
+                field(expr, &path, "body")?,
+                format!("{}.body", path),
+            )?;
+            Ok(Expr::Func(params, body.into(), span))
+        }
+        kind => Err(format!("{}: unknown kind {:?}", path, kind)),
+    }
+}
+
+fn from_span(value: &Value, path: &str) -> Result<Span, String> {
+    let Some(span) = value.get("span") else {
+        return Ok(Span::default());
+    };
+    let path = format!("{}.span", path);
+    Ok(Span {
+        start: from_position(
+            field(span, &path, "start")?,
+            format!("{}.start", path),
+        )?,
+        end: from_position(
+            field(span, &path, "end")?,
+            format!("{}.end", path),
+        )?,
+    })
+}
+
+fn from_position(value: &Value, path: String) -> Result<Position, String> {
+    let number = |key| match field(value, &path, key)?.as_u64() {
+        Some(n) => Ok(n as usize),
+        None => Err(format!("{}.{}: expected a number", path, key)),
+    };
+    Ok(Position {
+        offset: number("offset")?,
+        line: number("line")?,
+        column: number("column")?,
+    })
+}
+
+fn field<'a>(
+    value: &'a Value,
+    path: &str,
+    key: &str,
+) -> Result<&'a Value, String> {
+    value.get(key).ok_or_else(|| match path {
+        "" => format!("Missing {:?}", key),
+        path => format!("{}: missing {:?}", path, key),
+    })
+}
+
+fn string(value: &Value, path: &str, key: &str) -> Result<String, String> {
+    match field(value, path, key)?.as_str() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("{}.{}: expected a string", path, key)),
+    }
+}
+
+fn name(value: &Value, path: &str, key: &str) -> Result<String, String> {
+    let name = string(value, path, key)?;
+    if is_name(&name) {
+        Ok(name)
+    } else {
+        Err(format!("{}: invalid name {:?}", path, name))
+    }
+}
+
+fn array<'a>(
+    value: &'a Value,
+    path: &str,
+    key: &str,
+) -> Result<&'a Vec<Value>, String> {
+    match field(value, path, key)?.as_array() {
+        Some(array) => Ok(array),
+        None if path.is_empty() => Err(format!("{}: expected an array", key)),
+        None => Err(format!("{}.{}: expected an array", path, key)),
+    }
+}
+
+/// Names in source, and the parameter of the lambdas that right operator
+/// sections desugar into.
+fn is_name(s: &str) -> bool {
+    s == SECTION_PARAM || is(Rule::name, s)
+}
+
+/// Whether `s` is exactly one token of the atomic `rule`.
+fn is(rule: Rule, s: &str) -> bool {
+    PureParser::parse(rule, s).is_ok_and(|pairs| pairs.as_str() == s)
+}
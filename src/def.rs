@@ -1,9 +1,12 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
 
 use crate::ast::{Declaration, Expr, AST};
 use crate::stdlib;
 use std::collections::HashMap;
+use std::fmt;
+use std::iter::once;
 
 #[derive(Debug, PartialEq)]
 pub struct Program {
@@ -11,19 +14,25 @@ pub struct Program {
     index: HashMap<String, usize>,
 }
 
-impl From<AST> for Program {
-    fn from(ast: AST) -> Self {
+/// Fails on programs with lambdas other than top-level functions, whose
+/// parameters are the arguments of the definition. Lambdas within
+/// expressions, including operator sections, need closures, which the VM
+/// doesn't have yet.
+impl TryFrom<AST> for Program {
+    type Error = String;
+
+    fn try_from(ast: AST) -> Result<Self, Self::Error> {
         let definitions: Vec<Definition> = ast
             .get_declarations()
             .iter()
             .map(|decl| Definition::from(&ast, decl))
-            .collect();
+            .collect::<Result<_, _>>()?;
 
-        Self {
+        Ok(Self {
             definitions,
             index: stdlib::index(),
         }
-        .indexed()
+        .indexed())
     }
 }
 
@@ -42,6 +51,29 @@ impl Program {
     }
 }
 
+/// Readable dump of the program, for `purec emit def`: each definition at
+/// its offset in the code, with the ops that refer to other definitions or
+/// to builtins annotated with the offset or builtin id they resolve to.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for def in self.definitions.iter() {
+            writeln!(f, "{} @{}", def.id, self.index[&def.id])?;
+            for op in def.code.iter() {
+                match op {
+                    Op::PUSH_FN(id) => {
+                        writeln!(f, "  {} ; id {}", op, self.index[id])?
+                    }
+                    Op::PUSH_CMD(id) | Op::PUSH_CAF(id) => {
+                        writeln!(f, "  {} ; @{}", op, self.index[id])?
+                    }
+                    _ => writeln!(f, "  {}", op)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Definition {
     id: String,
@@ -49,56 +81,63 @@ pub struct Definition {
 }
 
 impl Definition {
-    fn from(ast: &AST, declaration: &Declaration) -> Self {
-        let expr = &declaration.expr;
-        let code = Self::argc(expr)
-            .into_iter()
-            .chain(Self::expand(ast, expr).into_iter())
-            .chain(vec![Op::RETURN].into_iter())
-            .collect();
-        Definition {
-            id: declaration.id.clone(),
-            code,
-        }
-    }
-
-    fn argc(expr: &Expr) -> Vec<Op> {
-        let count = match expr {
-            Expr::Func(params, _) => params.len() as u32,
-            _ => 0,
+    fn from(ast: &AST, declaration: &Declaration) -> Result<Self, String> {
+        let (params, body) = match &declaration.expr {
+            Expr::Func(params, body, _) => (params.as_slice(), &**body),
+            expr => (&[][..], expr),
         };
-        vec![Op::ARGC(count)]
+        let code = Self::expand(ast, params, body)
+            .map_err(|error| format!("{} in {}", error, declaration.id))?;
+        Ok(Definition {
+            id: declaration.id.clone(),
+            code: once(Op::ARGC(params.len() as u32))
+                .chain(code)
+                .chain(once(Op::RETURN))
+                .collect(),
+        })
     }
 
-    fn expand(ast: &AST, expr: &Expr) -> Vec<Op> {
-        match expr {
-            Expr::Int(i) => vec![Op::PUSH_I32(*i)],
-            Expr::Name(_) => todo!(),
-            Expr::ID(id) => {
+    /// Code that pushes the value of `expr`, where names are the `params` of
+    /// the definition.
+    fn expand(
+        ast: &AST,
+        params: &[String],
+        expr: &Expr,
+    ) -> Result<Vec<Op>, String> {
+        Ok(match expr {
+            Expr::Int(i, _) => vec![Op::PUSH_I32(*i)],
+            // Names are bound, and only by `params`, since other lambdas
+            // are rejected.
+            Expr::Name(name, _) => {
+                let index = params.iter().position(|param| param == name);
+                vec![Op::PUSH_ARG(index.unwrap() as u32)]
+            }
+            Expr::ID(id, _) => {
                 if id.starts_with("std.") {
                     vec![Op::PUSH_FN(id.clone())]
                 } else {
                     match ast.get_declaration(id).expr {
                         // Treat it as a closure.
-                        Expr::Func(_, _) => vec![Op::PUSH_CMD(id.clone())],
-                        // Treat it as value by calling it with a Unit argument.
-                        _ => vec![
-                            Op::PUSH_CMD(id.clone()),
-                            Op::PUSH_UNIT,
-                            Op::FEED(1),
-                        ],
+                        Expr::Func(_, _, _) => vec![Op::PUSH_CMD(id.clone())],
+                        // A constant, evaluated on first use and cached.
+                        _ => vec![Op::PUSH_CAF(id.clone())],
                     }
                 }
             }
-            Expr::Call(f, args) => Self::expand(ast, f)
-                .into_iter()
-                .chain(
-                    args.iter().map(|expr| Self::expand(ast, expr)).flatten(),
+            Expr::Call(f, args, _) => {
+                let mut code = Self::expand(ast, params, f)?;
+                for arg in args {
+                    code.extend(Self::expand(ast, params, arg)?);
+                }
+                code.push(Op::FEED(args.len() as u32));
+                code
+            }
+            Expr::Func(_, _, _) => {
+                return Err(
+                    "Lambdas are not supported by the VM target yet".into()
                 )
-                .chain(vec![Op::FEED(args.len() as u32)].into_iter())
-                .collect(),
-            Expr::Func(_, body) => Self::expand(ast, body),
-        }
+            }
+        })
     }
 }
 
@@ -116,9 +155,30 @@ pub enum Op {
     PUSH_CMD(String), // Push cmd onto the stack
     PUSH_ARG(u32),    // Push cmd argument (by its index) onto the stack
     DROP(u32),        // Drop top value off of the stack
+    PUSH_CAF(String), // Push the value of a constant, evaluating it once
 
     /* Program flow */
     FEED(u32), // FEED N top values into the function beneath
     BRANCH,    // BRANCH left or right based on a condition
     RETURN,    // Return from the routine
 }
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ARGC(n) => write!(f, "ARGC {}", n),
+            Self::PUSH_BOOL(b) => write!(f, "PUSH_BOOL {}", b),
+            Self::PUSH_U8(u) => write!(f, "PUSH_U8 {}", u),
+            Self::PUSH_I32(i) => write!(f, "PUSH_I32 {}", i),
+            Self::PUSH_FN(id) => write!(f, "PUSH_FN {}", id),
+            Self::PUSH_CMD(id) => write!(f, "PUSH_CMD {}", id),
+            Self::PUSH_ARG(index) => write!(f, "PUSH_ARG {}", index),
+            Self::DROP(n) => write!(f, "DROP {}", n),
+            Self::PUSH_CAF(id) => write!(f, "PUSH_CAF {}", id),
+            Self::FEED(n) => write!(f, "FEED {}", n),
+            Self::NOP | Self::PUSH_UNIT | Self::BRANCH | Self::RETURN => {
+                write!(f, "{:?}", self)
+            }
+        }
+    }
+}
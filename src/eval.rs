@@ -0,0 +1,410 @@
+use crate::ast::{Expr, AST, ENTRYPOINT};
+use crate::stdlib::{self, Builtin, StdLib};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Runs the program by interpreting its AST directly.
+///
+/// This is the reference for what a program means: the backends must agree
+/// with it on everything that it prints and on how it fails. Evaluation is
+/// strict, except for the arguments that builtins take unevaluated (see
+/// [`Builtin::lazy`]) in saturated calls, which is also when the JS backend
+/// passes them as thunks. Tail calls run in constant stack space.
+pub fn run(ast: &AST, out: &mut dyn Write) -> Result<(), Error> {
+    let evaluator = Evaluator::new(ast, out);
+    let main = evaluator.global(ENTRYPOINT)?;
+    evaluator.run(main)?;
+    Ok(())
+}
+
+/// Evaluates an expression in the context of the program, as the REPL does.
+/// A command is run, and evaluates to its result.
+pub fn eval<'a>(
+    ast: &'a AST,
+    expr: &'a Expr,
+    out: &'a mut dyn Write,
+) -> Result<Value<'a>, Error> {
+    let evaluator = Evaluator::new(ast, out);
+    match evaluator.eval(expr, Env::default())? {
+        cmd @ Value::Cmd(..) => evaluator.run(cmd),
+        value => Ok(value),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A builtin applied outside its domain, see [`StdLib`].
+    Panic { builtin: String, message: String },
+    /// A program that goes wrong in a way that no builtin checks for, e.g. by
+    /// applying an integer.
+    Runtime(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Panic { builtin, message } => {
+                write!(f, "panic in {}: {}", builtin, message)
+            }
+            Self::Runtime(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// How deeply evaluations may nest, which is how deep non-tail recursion can
+/// go, so that a program that recurses too deeply fails with an error rather
+/// than by overflowing the stack of the interpreter.
+pub const MAX_DEPTH: usize = 100_000;
+
+type Env<'a> = Rc<HashMap<&'a str, Value<'a>>>;
+
+#[derive(Clone)]
+pub enum Value<'a> {
+    Int(i32),
+    /// A lambda with the parameters that are still to be applied.
+    Closure(&'a [String], &'a Expr, Env<'a>),
+    /// A builtin with the arguments applied so far.
+    Builtin(&'static Builtin, Vec<Value<'a>>),
+    /// A command, made by a Cmd builtin out of its arguments. It only has an
+    /// effect when it is run.
+    Cmd(StdLib, Vec<Value<'a>>),
+    /// An argument that a builtin takes unevaluated.
+    Thunk(&'a Expr, Env<'a>),
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Closure(..) | Self::Builtin(..) => write!(f, "<function>"),
+            Self::Cmd(..) => write!(f, "<command>"),
+            Self::Thunk(..) => write!(f, "<thunk>"),
+        }
+    }
+}
+
+impl Value<'_> {
+    /// Type of the value, when it is known without type checking: that of
+    /// integers and of builtins that haven't been applied yet.
+    pub fn ty(&self) -> Option<&'static str> {
+        match self {
+            Self::Int(_) => Some("I32"),
+            Self::Builtin(builtin, applied) if applied.is_empty() => {
+                Some(builtin.ty)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What is left to do to get a value: nothing, or evaluating an expression
+/// in tail position, which [`Evaluator::eval`] does without recursing.
+enum Step<'a> {
+    Done(Value<'a>),
+    Eval(&'a Expr, Env<'a>),
+}
+
+struct Evaluator<'a> {
+    ast: &'a AST,
+    /// Values of the declarations evaluated so far, each computed at most
+    /// once.
+    globals: RefCell<HashMap<&'a str, Value<'a>>>,
+    /// Declarations whose value is being computed, which they must not
+    /// depend on.
+    evaluating: RefCell<HashSet<&'a str>>,
+    /// Number of evaluations in progress, up to [`MAX_DEPTH`].
+    depth: Cell<usize>,
+    out: RefCell<&'a mut dyn Write>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(ast: &'a AST, out: &'a mut dyn Write) -> Self {
+        Self {
+            ast,
+            globals: RefCell::new(HashMap::new()),
+            evaluating: RefCell::new(HashSet::new()),
+            depth: Cell::new(0),
+            out: RefCell::new(out),
+        }
+    }
+
+    fn global(&self, id: &'a str) -> Result<Value<'a>, Error> {
+        if let Some(builtin) = stdlib::builtin(id) {
+            return Ok(Value::Builtin(builtin, vec![]));
+        }
+        if let Some(value) = self.globals.borrow().get(id) {
+            return Ok(value.clone());
+        }
+        if !self.evaluating.borrow_mut().insert(id) {
+            return Err(Error::Runtime(format!("cyclic definition of {}", id)));
+        }
+        let value = self.eval(&self.ast.declarations[id].expr, Env::default());
+        self.evaluating.borrow_mut().remove(id);
+        let value = value?;
+        self.globals.borrow_mut().insert(id, value.clone());
+        Ok(value)
+    }
+
+    fn eval(&self, expr: &'a Expr, env: Env<'a>) -> Result<Value<'a>, Error> {
+        if self.depth.get() == MAX_DEPTH {
+            return Err(Error::Runtime(format!(
+                "stack overflow: evaluation nested more than {} deep",
+                MAX_DEPTH
+            )));
+        }
+        self.depth.set(self.depth.get() + 1);
+        let value = self.eval_loop(expr, env);
+        self.depth.set(self.depth.get() - 1);
+        value
+    }
+
+    /// [`Evaluator::eval`] without the depth check.
+    fn eval_loop(
+        &self,
+        mut expr: &'a Expr,
+        mut env: Env<'a>,
+    ) -> Result<Value<'a>, Error> {
+        loop {
+            let step = match expr {
+                Expr::Int(i, _) => Step::Done(Value::Int(*i)),
+                Expr::Name(name, _) => Step::Done(env[name.as_str()].clone()),
+                Expr::ID(id, _) => Step::Done(self.global(id)?),
+                Expr::Func(params, body, _) => {
+                    Step::Done(Value::Closure(params, body, env))
+                }
+                Expr::Call(..) => {
+                    let (f, args) = expr.flatten();
+                    let lazy = self.ast.lazy_params(f, args.len());
+                    let f = self.eval(f, env.clone())?;
+                    let args = args
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, arg)| match lazy.contains(&i) {
+                            true => Ok(Value::Thunk(arg, env.clone())),
+                            false => self.eval(arg, env.clone()),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    self.apply(f, args)?
+                }
+            };
+            match step {
+                Step::Done(value) => return Ok(value),
+                Step::Eval(next, next_env) => {
+                    expr = next;
+                    env = next_env;
+                }
+            }
+        }
+    }
+
+    fn call(
+        &self,
+        f: Value<'a>,
+        args: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, Error> {
+        let step = self.apply(f, args)?;
+        self.finish(step)
+    }
+
+    fn finish(&self, step: Step<'a>) -> Result<Value<'a>, Error> {
+        match step {
+            Step::Done(value) => Ok(value),
+            Step::Eval(expr, env) => self.eval(expr, env),
+        }
+    }
+
+    fn apply(
+        &self,
+        f: Value<'a>,
+        mut args: Vec<Value<'a>>,
+    ) -> Result<Step<'a>, Error> {
+        match f {
+            Value::Closure(params, body, env) => {
+                let bound = params.len().min(args.len());
+                let rest = args.split_off(bound);
+                let mut env = (*env).clone();
+                env.extend(params.iter().map(String::as_str).zip(args));
+                let env = Rc::new(env);
+                if bound < params.len() {
+                    Ok(Step::Done(Value::Closure(&params[bound..], body, env)))
+                } else if rest.is_empty() {
+                    Ok(Step::Eval(body, env))
+                } else {
+                    let f = self.eval(body, env)?;
+                    self.apply(f, rest)
+                }
+            }
+            Value::Builtin(builtin, mut applied) => {
+                applied.append(&mut args);
+                if applied.len() < builtin.arity {
+                    return Ok(Step::Done(Value::Builtin(builtin, applied)));
+                }
+                let rest = applied.split_off(builtin.arity);
+                let step = self.builtin(builtin, applied)?;
+                if rest.is_empty() {
+                    Ok(step)
+                } else {
+                    let f = self.finish(step)?;
+                    self.apply(f, rest)
+                }
+            }
+            value => Err(Error::Runtime(format!(
+                "Cannot apply {} to arguments",
+                value
+            ))),
+        }
+    }
+
+    /// Applies a builtin to exactly as many arguments as it takes.
+    fn builtin(
+        &self,
+        builtin: &'static Builtin,
+        args: Vec<Value<'a>>,
+    ) -> Result<Step<'a>, Error> {
+        let mut args = args.into_iter();
+        let mut arg = || args.next().unwrap();
+        let value = match builtin.vm {
+            StdLib::ID => arg(),
+            StdLib::Iff => {
+                let (cond, x, y) = (arg(), arg(), arg());
+                // Anything but 0 counts as true, as in JS.
+                let chosen = match cond {
+                    Value::Int(0) => y,
+                    _ => x,
+                };
+                return Ok(match chosen {
+                    Value::Thunk(expr, env) => Step::Eval(expr, env),
+                    value => Step::Done(value),
+                });
+            }
+            StdLib::Add_I32 => {
+                let (x, y) = (int(arg())?, int(arg())?);
+                Value::Int(x.wrapping_add(y))
+            }
+            StdLib::Sub_I32 => {
+                let (x, y) = (int(arg())?, int(arg())?);
+                Value::Int(x.wrapping_sub(y))
+            }
+            StdLib::Mul_I32 => {
+                let (x, y) = (int(arg())?, int(arg())?);
+                Value::Int(x.wrapping_mul(y))
+            }
+            StdLib::Div_I32 => {
+                let (x, y) = (int(arg())?, int(arg())?);
+                if y == 0 {
+                    return Err(Error::Panic {
+                        builtin: builtin.id(),
+                        message: "division by zero".to_string(),
+                    });
+                }
+                Value::Int(x.wrapping_div(y))
+            }
+            StdLib::Prints
+            | StdLib::Cmd
+            | StdLib::MapCmd
+            | StdLib::SwapCmd
+            | StdLib::ThenCmd
+            | StdLib::ChainCmd => Value::Cmd(
+                builtin.vm,
+                (0..builtin.arity).map(|_| arg()).collect(),
+            ),
+        };
+        Ok(Step::Done(value))
+    }
+
+    /// Runs a command for its effects and returns its result.
+    ///
+    /// Combined commands are taken apart in a loop, keeping what is left to
+    /// do with the result of the one that runs first, so that long sequences
+    /// of commands run in constant stack space.
+    fn run(&self, mut cmd: Value<'a>) -> Result<Value<'a>, Error> {
+        let mut pending = vec![];
+        loop {
+            let Value::Cmd(kind, args) = cmd else {
+                return Err(Error::Runtime(format!(
+                    "Cannot run {}, it is not a command",
+                    cmd
+                )));
+            };
+            let mut args = args.into_iter();
+            let mut arg = || args.next().unwrap();
+            let mut value = match kind {
+                StdLib::Prints => {
+                    let value = arg();
+                    writeln!(self.out.borrow_mut(), "{}", value).map_err(
+                        |error| {
+                            Error::Runtime(format!(
+                                "Failed to print: {}",
+                                error
+                            ))
+                        },
+                    )?;
+                    value
+                }
+                StdLib::Cmd => arg(),
+                StdLib::MapCmd => {
+                    let f = arg();
+                    cmd = arg();
+                    pending.push(Pending::Map(f));
+                    continue;
+                }
+                StdLib::SwapCmd => {
+                    let value = arg();
+                    cmd = arg();
+                    pending.push(Pending::Swap(value));
+                    continue;
+                }
+                StdLib::ThenCmd => {
+                    cmd = arg();
+                    pending.push(Pending::Then(arg()));
+                    continue;
+                }
+                StdLib::ChainCmd => {
+                    cmd = arg();
+                    pending.push(Pending::Chain(arg()));
+                    continue;
+                }
+                _ => unreachable!("{:?} does not make commands", kind),
+            };
+            cmd = loop {
+                match pending.pop() {
+                    None => return Ok(value),
+                    Some(Pending::Map(f)) => {
+                        value = self.call(f, vec![value])?
+                    }
+                    Some(Pending::Swap(swapped)) => value = swapped,
+                    Some(Pending::Then(next)) => break next,
+                    Some(Pending::Chain(f)) => {
+                        break self.call(f, vec![value])?
+                    }
+                }
+            };
+        }
+    }
+}
+
+/// What is left to do with the result of a command that runs first as part
+/// of a combined one.
+enum Pending<'a> {
+    /// Apply the function to it, for `std.mapCmd`.
+    Map(Value<'a>),
+    /// Replace it, for `std.swapCmd`.
+    Swap(Value<'a>),
+    /// Run the next command, for `std.thenCmd`.
+    Then(Value<'a>),
+    /// Run the command the function makes of it, for `std.chainCmd`.
+    Chain(Value<'a>),
+}
+
+fn int(value: Value) -> Result<i32, Error> {
+    match value {
+        Value::Int(i) => Ok(i),
+        value => Err(Error::Runtime(format!(
+            "Expected an integer, got {}",
+            value
+        ))),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::ast::Expr;
+use crate::parser::Rule;
+use pest::iterators::Pair;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,  // infixl
+    Right, // infixr
+    None,  // infix
+}
+
+/// Printed as the keyword that declares it.
+impl fmt::Display for Assoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Left => write!(f, "infixl"),
+            Self::Right => write!(f, "infixr"),
+            Self::None => write!(f, "infix"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixity {
+    pub assoc: Assoc,
+    pub precedence: u8,
+}
+
+/// Operators without a fixity declaration behave as `infixl 9`.
+impl Default for Fixity {
+    fn default() -> Self {
+        Self {
+            assoc: Assoc::Left,
+            precedence: 9,
+        }
+    }
+}
+
+impl Fixity {
+    /// Parses an `infixl 6 +;` declaration into the operator and its fixity.
+    pub fn parse(pair: Pair<Rule>) -> (String, Self) {
+        let mut it = pair.into_inner();
+        let assoc = match it.next().unwrap().as_str() {
+            "infixl" => Assoc::Left,
+            "infixr" => Assoc::Right,
+            _ => Assoc::None,
+        };
+        let precedence = it.next().unwrap().as_str().parse().unwrap();
+        let op = it.next().unwrap().as_str().to_string();
+        (op, Self { assoc, precedence })
+    }
+}
+
+pub type Fixities = HashMap<String, Fixity>;
+
+/// Resolves a flat `a + b * c` chain into nested calls of the operators using
+/// precedence climbing. Operators are given as `Expr::ID`s.
+pub fn resolve(
+    fixities: &Fixities,
+    operands: Vec<Expr>,
+    ops: Vec<Expr>,
+) -> Result<Expr, String> {
+    let mut operands = operands.into_iter();
+    let mut ops = ops.into_iter().peekable();
+    let lhs = operands.next().unwrap();
+    climb(fixities, lhs, 0, &mut operands, &mut ops)
+}
+
+fn climb(
+    fixities: &Fixities,
+    mut lhs: Expr,
+    min_precedence: u8,
+    operands: &mut IntoIter<Expr>,
+    ops: &mut Peekable<IntoIter<Expr>>,
+) -> Result<Expr, String> {
+    while let Some(op) =
+        ops.next_if(|op| fixity_of(fixities, op).precedence >= min_precedence)
+    {
+        let fixity = fixity_of(fixities, &op);
+        let mut rhs = operands.next().unwrap();
+        while let Some(next) = ops.peek() {
+            let next_fixity = fixity_of(fixities, next);
+            if next_fixity.precedence > fixity.precedence {
+                rhs =
+                    climb(fixities, rhs, fixity.precedence + 1, operands, ops)?;
+            } else if next_fixity.precedence < fixity.precedence {
+                break;
+            } else if fixity.assoc != next_fixity.assoc
+                || fixity.assoc == Assoc::None
+            {
+                return Err(format!(
+                    "Cannot mix '{}' and '{}' of the same precedence {} \
+                     without parentheses",
+                    name(&op),
+                    name(next),
+                    fixity.precedence
+                ));
+            } else if fixity.assoc == Assoc::Right {
+                rhs = climb(fixities, rhs, fixity.precedence, operands, ops)?;
+            } else {
+                break;
+            }
+        }
+        let span = lhs.span().to(rhs.span());
+        lhs = Expr::Call(Box::new(op), vec![lhs, rhs], span);
+    }
+    Ok(lhs)
+}
+
+fn fixity_of(fixities: &Fixities, op: &Expr) -> Fixity {
+    fixities.get(name(op)).copied().unwrap_or_default()
+}
+
+fn name(op: &Expr) -> &str {
+    match op {
+        Expr::ID(name, _) => name,
+        _ => unreachable!("operator is an id"),
+    }
+}
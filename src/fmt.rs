@@ -0,0 +1,306 @@
+use crate::parser::{self, PureParser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+
+const WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+/// Prints source in the canonical style: one fixity or declaration per line,
+/// wrapped at 80 columns, with at most one blank line between them and only
+/// the brackets that are needed.
+///
+/// Comments between declarations are kept where they are. A declaration with
+/// comments inside is left as it was written, because there would be no
+/// telling which part of it they are about once it is laid out anew.
+/// Formatting formatted source changes nothing.
+pub fn format(input: &str) -> Result<String, String> {
+    let items: Vec<Pair<Rule>> = PureParser::parse(Rule::file, input)
+        .map_err(|syntax_error| format!("Syntax error:\n{}", syntax_error))?
+        .take_while(parser::is_not_eoi)
+        .collect();
+
+    let mut output = String::new();
+    let mut last = 0;
+    for item in items {
+        let span = item.as_span();
+        gap(&mut output, &input[last..span.start()], true);
+        if span.as_str().contains("{-") {
+            output.push_str(span.as_str());
+        } else {
+            output.push_str(&render(&top_level(item)));
+        }
+        last = span.end();
+    }
+    gap(&mut output, &input[last..], false);
+    output.push('\n');
+    Ok(output)
+}
+
+/// Prints the comments in the gap before an item, or the end of the file. A
+/// comment on the same line as the previous item stays there, the others
+/// start a line, and blank lines are kept, though never more than one.
+fn gap(output: &mut String, mut gap: &str, before_item: bool) {
+    loop {
+        let text = gap.trim_start();
+        let whitespace = &gap[..gap.len() - text.len()];
+        let comment = text.starts_with("{-");
+        if !output.is_empty() && (comment || before_item) {
+            match whitespace.matches('\n').count() {
+                0 if comment => output.push(' '),
+                0 | 1 => output.push('\n'),
+                _ => output.push_str("\n\n"),
+            }
+        }
+        if !comment {
+            return;
+        }
+        let end = text.find("-}").map_or(text.len(), |end| end + 2);
+        output.push_str(&text[..end]);
+        gap = &text[end..];
+    }
+}
+
+/// Layout of the code before it is fitted to the line width.
+#[derive(Clone, Debug)]
+enum Doc {
+    Text(String),
+    /// A space, or a line break if the enclosing group doesn't fit.
+    Line,
+    /// Indents the line breaks within.
+    Nest(Box<Doc>),
+    /// Laid out on a single line if it fits, otherwise its lines break.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(s: &str) -> Doc {
+    Doc::Text(s.to_string())
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(doc.into())
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(doc.into())
+}
+
+fn brackets(doc: Doc) -> Doc {
+    concat(vec![text("("), nest(doc), text(")")])
+}
+
+fn top_level(pair: Pair<Rule>) -> Doc {
+    let rule = pair.as_rule();
+    let mut pairs = pair.into_inner();
+    match rule {
+        Rule::fixity => {
+            let words: Vec<&str> = pairs.map(|pair| pair.as_str()).collect();
+            text(&format!("{};", words.join(" ")))
+        }
+        Rule::declaration => {
+            let id = name(pairs.next().unwrap());
+            let expr = pairs.next().unwrap();
+            match expr.as_rule() {
+                // Lambdas and do blocks break within themselves.
+                Rule::func | Rule::do_block => concat(vec![
+                    text(&format!("{} := ", id)),
+                    expression(expr),
+                    text(";"),
+                ]),
+                _ => group(concat(vec![
+                    text(&format!("{} :=", id)),
+                    nest(concat(vec![Doc::Line, expression(expr)])),
+                    text(";"),
+                ])),
+            }
+        }
+        _ => unreachable!("top-level {:?}", rule),
+    }
+}
+
+/// Declared ids and operators, the latter in brackets.
+fn name(pair: Pair<Rule>) -> String {
+    match pair.as_rule() {
+        Rule::operator => {
+            format!("({})", pair.into_inner().next().unwrap().as_str())
+        }
+        _ => pair.as_str().to_string(),
+    }
+}
+
+fn expression(pair: Pair<Rule>) -> Doc {
+    let rule = pair.as_rule();
+    match rule {
+        Rule::int | Rule::name | Rule::id | Rule::operator => text(&name(pair)),
+        Rule::left_section => {
+            let mut pairs = pair.into_inner();
+            let operand = operand(pairs.next().unwrap());
+            let ops = pairs.next().unwrap().as_str();
+            concat(vec![text("("), operand, text(&format!(" {})", ops))])
+        }
+        Rule::right_section => {
+            let mut pairs = pair.into_inner();
+            let ops = pairs.next().unwrap().as_str();
+            let operand = operand(pairs.next().unwrap());
+            concat(vec![text(&format!("({} ", ops)), operand, text(")")])
+        }
+        Rule::call => {
+            let mut pairs = pair.into_inner();
+            let f = callee(pairs.next().unwrap());
+            let args = pairs.flat_map(|arg| vec![Doc::Line, argument(arg)]);
+            group(concat(vec![f, nest(concat(args.collect()))]))
+        }
+        Rule::func => {
+            let (params, body): (Vec<_>, Vec<_>) =
+                pair.into_inner().partition(parser::is_param);
+            let params: Vec<String> = params
+                .into_iter()
+                .map(|param| format!("{} ->", param.into_inner().as_str()))
+                .collect();
+            let body = expression(body.into_iter().next().unwrap());
+            group(concat(vec![
+                text(&params.join(" ")),
+                nest(concat(vec![Doc::Line, body])),
+            ]))
+        }
+        Rule::binop => {
+            let docs = pair
+                .into_inner()
+                .map(|pair| match pair.as_rule() {
+                    Rule::ops => concat(vec![
+                        Doc::Line,
+                        text(&format!("{} ", pair.as_str())),
+                    ]),
+                    _ => operand(pair),
+                })
+                .collect();
+            group(concat(docs))
+        }
+        Rule::do_block => {
+            let mut statements = vec![];
+            for (i, statement) in pair.into_inner().skip(1).enumerate() {
+                if i > 0 {
+                    statements.push(text(";"));
+                }
+                statements.push(Doc::Line);
+                statements.push(self::statement(statement));
+            }
+            group(concat(vec![
+                text("do {"),
+                nest(concat(statements)),
+                Doc::Line,
+                text("}"),
+            ]))
+        }
+        _ => unreachable!("expression {:?}", rule),
+    }
+}
+
+fn statement(pair: Pair<Rule>) -> Doc {
+    match pair.as_rule() {
+        Rule::bind => {
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str();
+            concat(vec![
+                text(&format!("{} <- ", name)),
+                expression(pairs.next().unwrap()),
+            ])
+        }
+        Rule::pure => concat(vec![
+            text("pure "),
+            argument(pair.into_inner().nth(1).unwrap()),
+        ]),
+        _ => expression(pair),
+    }
+}
+
+/// Brackets are not part of the parse tree, so they are put back wherever
+/// the grammar requires them.
+fn callee(pair: Pair<Rule>) -> Doc {
+    match pair.as_rule() {
+        Rule::call | Rule::func | Rule::binop | Rule::do_block | Rule::int => {
+            brackets(expression(pair))
+        }
+        _ => expression(pair),
+    }
+}
+
+fn argument(pair: Pair<Rule>) -> Doc {
+    match pair.as_rule() {
+        Rule::call | Rule::func | Rule::binop => brackets(expression(pair)),
+        _ => expression(pair),
+    }
+}
+
+fn operand(pair: Pair<Rule>) -> Doc {
+    match pair.as_rule() {
+        Rule::func | Rule::binop => brackets(expression(pair)),
+        _ => expression(pair),
+    }
+}
+
+fn render(doc: &Doc) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                output.push_str(s);
+                column += s.chars().count();
+            }
+            Doc::Line if flat => {
+                output.push(' ');
+                column += 1;
+            }
+            Doc::Line => {
+                output.push('\n');
+                output.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::Nest(doc) => stack.push((indent + INDENT, flat, doc)),
+            Doc::Group(doc) => {
+                let flat =
+                    flat || fits(WIDTH.saturating_sub(column), doc, &stack);
+                stack.push((indent, flat, doc));
+            }
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)))
+            }
+        }
+    }
+    output
+}
+
+/// Whether the group fits on the rest of the line when laid out flat, along
+/// with whatever follows it up to the next line break.
+fn fits(width: usize, group: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut width = width as isize;
+    let mut stack: Vec<(bool, &Doc)> = vec![(true, group)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (flat, doc) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some((_, flat, doc)) => (*flat, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => width -= s.chars().count() as isize,
+            Doc::Line if flat => width -= 1,
+            Doc::Line => return true,
+            Doc::Nest(doc) | Doc::Group(doc) => stack.push((flat, doc)),
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|d| (flat, d)))
+            }
+        }
+        if width < 0 {
+            return false;
+        }
+    }
+}
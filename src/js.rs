@@ -1,24 +1,136 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
-
-use crate::ast::{Declaration, Expr, AST};
-
-pub struct Program(Vec<JS>);
-
-impl From<AST> for Program {
-    fn from(ast: AST) -> Self {
-        let require_std = require("./std").constt("std");
-        let module: Vec<JS> =
-            ast.get_declarations().into_iter().map(JS::from).collect();
-        let execute = JS::name("main").call(vec![]).call(vec![]);
-        Self(
-            vec![require_std]
-                .into_iter()
-                .chain(module.into_iter())
-                .chain(vec![execute].into_iter())
-                .collect(),
-        )
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::iter::once;
+
+use clap::ValueEnum;
+
+use crate::ast::{Declaration, Expr, Span, AST, ENTRYPOINT};
+use crate::stdlib;
+
+mod minify;
+mod print;
+mod runtime;
+mod sourcemap;
+
+pub use print::Mapping;
+pub use sourcemap::SourceMap;
+
+/// Module system of the generated code and of the std runtime it loads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Module {
+    /// CommonJS: `require` and `module.exports`
+    #[default]
+    Cjs,
+    /// ECMAScript modules: `import` and `export`
+    Esm,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub module: Module,
+    /// Compile a library: the entrypoint is not run and only `exports` are
+    /// exported, or every public declaration if `exports` is empty.
+    pub lib: bool,
+    pub exports: Vec<String>,
+    /// Inline the parts of the std runtime that the program uses instead of
+    /// loading it from a separate file.
+    pub bundle: bool,
+    /// Leave out all optional whitespace and shorten local names.
+    pub minify: bool,
+    /// Run tail calls between top-level functions on a trampoline, so that
+    /// mutually recursive functions don't grow the stack.
+    pub trampoline: bool,
+}
+
+impl Options {
+    fn exported(&self, declarations: &[Declaration]) -> Vec<String> {
+        if self.lib && !self.exports.is_empty() {
+            return self.exports.clone();
+        }
+        declarations
+            .iter()
+            .map(|decl| decl.id.clone())
+            .filter(|id| !self.lib || is_public(id))
+            .collect()
+    }
+}
+
+/// Declarations whose name starts with an underscore, e.g. `main._helper`, are
+/// private to the module.
+fn is_public(id: &str) -> bool {
+    !id.rsplit('.').next().unwrap().starts_with('_')
+}
+
+pub struct Program {
+    statements: Vec<JS>,
+    minify: bool,
+}
+
+impl Program {
+    pub fn new(ast: AST, options: &Options) -> Self {
+        let declarations = ast.get_declarations();
+        let codegen = Codegen::new(&declarations, options.trampoline);
+        let std = if options.bundle {
+            let arities = stdlib::arities();
+            let used = ast
+                .get_std_ids()
+                .iter()
+                .flat_map(|id| {
+                    let name = id.trim_start_matches("std.");
+                    let uncurried = arities
+                        .get(id)
+                        .map(|arity| format!("{}${}", name, arity));
+                    std::iter::once(name.to_string()).chain(uncurried)
+                })
+                .chain(codegen.runtime())
+                .chain(Some("run".to_string()).filter(|_| !options.lib))
+                .collect();
+            let runtime = runtime::bundle(&used);
+            JS::Raw(if options.minify {
+                minify::raw(&runtime)
+            } else {
+                runtime
+            })
+        } else {
+            import_std(options.module)
+        };
+        let namespaces =
+            namespaces(&options.exported(&declarations), &codegen.cafs);
+        let module = codegen
+            .ordered(declarations)
+            .into_iter()
+            .flat_map(|decl| codegen.declaration(decl));
+        let exports = match options.module {
+            Module::Cjs => vec![JS::Object(namespaces).exports()],
+            Module::Esm => export(namespaces),
+        };
+        // The runtime evaluates the entrypoint and runs the command, and
+        // reports panics from either.
+        let execute = match ast.declarations.get(ENTRYPOINT) {
+            Some(entrypoint) if !options.lib => vec![JS::Mapped(
+                entrypoint.span,
+                JS::name("std.run")
+                    .call(vec![JS::name(&mangle(ENTRYPOINT))])
+                    .into(),
+            )],
+            _ => vec![],
+        };
+        let statements = vec![std]
+            .into_iter()
+            .chain(module)
+            .chain(exports)
+            .chain(execute)
+            .collect();
+        Self {
+            statements: if options.minify {
+                minify::shorten_locals(statements)
+            } else {
+                statements
+            },
+            minify: options.minify,
+        }
     }
 }
 
@@ -47,6 +159,26 @@ impl JS {
         Self::Call(self.into(), args)
     }
 
+    fn apply(self, args: Vec<Self>) -> Self {
+        Self::Apply(self.into(), args)
+    }
+
+    fn assign(self, name: &str) -> Self {
+        Self::Assign(name.into(), self.into())
+    }
+
+    fn continues(&self) -> bool {
+        match self {
+            Self::Continue => true,
+            Self::If(_, then, otherwise) => {
+                then.continues() || otherwise.continues()
+            }
+            Self::Proc(statements) => statements.iter().any(Self::continues),
+            Self::Mapped(_, js) => js.continues(),
+            _ => false,
+        }
+    }
+
     fn name(name: &str) -> Self {
         Self::Name(name.into())
     }
@@ -59,11 +191,159 @@ impl JS {
         )
     }
 
+    fn dump(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let (label, children): (String, Vec<&Self>) = match self {
+            Self::Assign(name, body) => {
+                (format!("Assign {}", name), vec![body])
+            }
+            Self::Call(f, args) => {
+                ("Call".into(), once(&**f).chain(args).collect())
+            }
+            Self::Apply(f, args) => {
+                ("Apply".into(), once(&**f).chain(args).collect())
+            }
+            Self::Kw(kw, body) => (format!("Kw {}", kw), vec![body]),
+            Self::Function(name, params, body) => (
+                format!("Function {}({})", name, params.join(", ")),
+                vec![body],
+            ),
+            Self::Func(params, body) if params.is_empty() => {
+                ("Func".into(), vec![body])
+            }
+            Self::Func(params, body) => {
+                (format!("Func {}", params.join(" ")), vec![body])
+            }
+            Self::Cond(cond, x, y) => ("Cond".into(), vec![cond, x, y]),
+            Self::Proc(statements) => {
+                ("Proc".into(), statements.iter().collect())
+            }
+            Self::If(cond, x, y) => ("If".into(), vec![cond, x, y]),
+            Self::Loop(body) => ("Loop".into(), vec![body]),
+            Self::Getter(body) => ("Getter".into(), vec![body]),
+            Self::Continue => ("Continue".into(), vec![]),
+            Self::Object(fields) => {
+                writeln!(f, "{}Object", indent)?;
+                for (key, value) in fields {
+                    writeln!(f, "{}  {}:", indent, key)?;
+                    value.dump(f, depth + 2)?;
+                }
+                return Ok(());
+            }
+            Self::Import(name, path) => {
+                (format!("Import {} {:?}", name, path), vec![])
+            }
+            Self::Export(names) => {
+                let names: Vec<String> = names
+                    .iter()
+                    .map(|(local, name)| format!("{} as {}", local, name))
+                    .collect();
+                (format!("Export {}", names.join(", ")), vec![])
+            }
+            Self::Raw(code) => (
+                format!(
+                    "Raw {} lines: {}",
+                    code.lines().count(),
+                    code.lines().next().unwrap_or_default()
+                ),
+                vec![],
+            ),
+            Self::Mapped(span, js) => (format!("Mapped {}", span), vec![js]),
+            Self::Name(name) => (format!("Name {}", name), vec![]),
+            Self::Str(s) => (format!("Str {:?}", s), vec![]),
+            Self::Int(i) => (format!("Int {}", i), vec![]),
+        };
+        writeln!(f, "{}{}", indent, label)?;
+        children.iter().try_for_each(|js| js.dump(f, depth + 1))
+    }
+
     fn str(s: &str) -> JS {
         Self::Str(s.into())
     }
 }
 
+/// Source of the std runtime for the module system.
+pub fn std_module(module: Module) -> String {
+    runtime::std(module)
+}
+
+fn import_std(module: Module) -> JS {
+    match module {
+        Module::Cjs => require("./std").constt("std"),
+        Module::Esm => JS::Import("std".into(), "./std.js".into()),
+    }
+}
+
+/// Groups exported declarations by the first segment of their id, so that
+/// `main.fst` is reachable as `main.fst` from the outside rather than by its
+/// mangled name. The objects are flat: the rest of the id is one key, e.g.
+/// `main.util.half` is `main["util.half"]`, since `main.util` may itself be a
+/// declaration. Ids without a namespace (operators) are exposed as they are
+/// mangled.
+///
+/// Constants are exported as their value rather than the cell that holds it,
+/// through a getter, so that they are still computed on first use only.
+fn namespaces(ids: &[String], cafs: &HashSet<String>) -> Vec<(String, JS)> {
+    let mut roots: BTreeMap<String, Vec<(String, JS)>> = BTreeMap::new();
+    let mut bare = vec![];
+    for id in ids {
+        let value = if cafs.contains(id) {
+            JS::Getter(JS::Name(mangle(id)).call(vec![]).into())
+        } else {
+            JS::Name(mangle(id))
+        };
+        match id.split_once('.') {
+            Some((root, rest)) => roots
+                .entry(root.to_string())
+                .or_default()
+                .push((rest.to_string(), value)),
+            None => bare.push((mangle(id), value)),
+        }
+    }
+    bare.sort_by(|a, b| a.0.cmp(&b.0));
+    roots
+        .into_iter()
+        .map(|(root, mut members)| {
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+            (root, JS::Object(members))
+        })
+        .chain(bare)
+        .collect()
+}
+
+/// ESM exports: namespace objects are bound to a local first, since only
+/// declarations and bindings can be exported by name. For the same reason,
+/// constants outside of a namespace are computed when the module loads, and
+/// their value is exported.
+fn export(exports: Vec<(String, JS)>) -> Vec<JS> {
+    let mut statements = vec![];
+    let mut names = vec![];
+    for (name, value) in exports {
+        match value {
+            JS::Name(local) => names.push((local, name)),
+            JS::Getter(value) => {
+                let local = format!("{}$", name);
+                statements.push(value.constt(&local));
+                names.push((local, name));
+            }
+            object => {
+                let local = namespace_local(&name);
+                statements.push(object.constt(&local));
+                names.push((local, name));
+            }
+        }
+    }
+    statements.push(JS::Export(names));
+    statements
+}
+
+/// The local that a namespace object is bound to, e.g. `$ns$console`. A
+/// namespace can be named like a global, which the bundled runtime relies
+/// on, while no Pure id mangles to a name with this prefix.
+fn namespace_local(root: &str) -> String {
+    format!("$ns${}", root)
+}
+
 fn require(path: &str) -> JS {
     JS::Call(
         JS::Name("require".into()).into(),
@@ -71,147 +351,533 @@ fn require(path: &str) -> JS {
     )
 }
 
-impl Into<String> for Program {
-    fn into(self) -> String {
-        let defs: Vec<String> =
-            self.0.into_iter().map(|def| def.into()).collect();
-        defs.join("\n\n")
+impl Program {
+    /// Renders the code along with the mappings back to the Pure source.
+    pub fn render(self) -> (String, Vec<Mapping>) {
+        print::print(self.statements, self.minify)
+    }
+}
+
+/// Readable dump of the JS tree before it is printed, for `purec emit js`:
+/// one node per line, indented under its parent. Raw code, such as the
+/// bundled runtime, is shown by its first line.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.statements.iter().try_for_each(|js| js.dump(f, 0))
+    }
+}
+
+impl From<Program> for String {
+    fn from(program: Program) -> Self {
+        program.render().0
     }
 }
 
 #[derive(PartialEq, Eq, Debug)]
 enum JS {
-    Assign(String, Box<Self>),  // something = body;
-    Call(Box<Self>, Vec<Self>), // f (x) (y) (z)
-    Kw(String, Box<Self>),      // new ... or typeof ...
+    Assign(String, Box<Self>),   // something = body;
+    Call(Box<Self>, Vec<Self>),  // f (x) (y) (z)
+    Apply(Box<Self>, Vec<Self>), // f(x, y, z)
+    Kw(String, Box<Self>),       // new ... or typeof ...
     Function(String, Vec<String>, Box<Self>), // function name(x, y, z) { body }
     Func(Vec<String>, Box<Self>), // x => y => z => body
-    Proc(Vec<Self>),            // { a; list; of; statements; }
-    Object(HashMap<String, Self>), // { x: 1, y: "hello" }
-    Name(String),               // x
-    Str(String),                // "hello"
-    Int(i32),                   // -42
+    Cond(Box<Self>, Box<Self>, Box<Self>), // cond ? x : y
+    Proc(Vec<Self>),             // { a; list; of; statements; }
+    If(Box<Self>, Box<Self>, Box<Self>), // if (cond) { ... } else { ... }
+    Loop(Box<Self>),             // for (;;) { ... }
+    Continue,                    // continue;
+    Object(Vec<(String, Self)>), // { x: 1, y: "hello" }
+    Getter(Box<Self>),           // get x() { return body; }, in an Object
+    Import(String, String),      // import * as name from "path";
+    Export(Vec<(String, String)>), // export { local as name };
+    Raw(String),                 // code that is emitted verbatim
+    Mapped(Span, Box<Self>),     // JS generated from the Pure code at Span
+    Name(String),                // x
+    Str(String),                 // "hello"
+    Int(i32),                    // -42
+}
+
+/// Translates declarations and expressions, calling functions through their
+/// uncurried entry point where the arguments allow it.
+struct Codegen {
+    /// JS name and arity of the uncurried entry point of top-level functions
+    /// and std builtins, by Pure id.
+    uncurried: HashMap<String, (String, usize)>,
+    /// Parameters that uncurried entry points take as thunks, by JS name.
+    lazy: HashMap<String, Vec<usize>>,
+    /// Constant applicative forms: declarations that compute a value without
+    /// taking parameters. Each is a cell that computes the value on first
+    /// use, and references to them get the value by calling it.
+    cafs: HashSet<String>,
+    trampoline: bool,
 }
 
-impl From<Declaration> for JS {
-    fn from(decl: Declaration) -> Self {
-        match decl.expr.clone() {
-            Expr::Func(ps, expr) => {
-                let param = &ps[0].clone();
-                Self::Proc(vec![Self::from(reduce_func(*ps, *expr)).returns()])
-                    .function(&decl.id, vec![param])
+impl Codegen {
+    fn new(declarations: &[Declaration], trampoline: bool) -> Self {
+        let mut uncurried: HashMap<String, (String, usize)> = stdlib::arities()
+            .into_iter()
+            .map(|(id, arity)| {
+                let name = uncurried_name(&id, arity);
+                (id, (name, arity))
+            })
+            .collect();
+        // On a trampoline even functions of one parameter need an entry
+        // point that may bounce, apart from the one other code calls.
+        let min_arity = if trampoline { 1 } else { 2 };
+        for decl in declarations {
+            if let Expr::Func(ps, _, _) = &decl.expr {
+                if ps.len() >= min_arity {
+                    let name = uncurried_name(&decl.id, ps.len());
+                    uncurried.insert(decl.id.clone(), (name, ps.len()));
+                }
             }
-            Expr::Call(f, args) => {
-                Self::Proc(vec![Self::from(Expr::Call(f, args)).returns()])
-                    .function(&decl.id, vec![])
+        }
+        // Aliases such as `(+) := std.add;` call the target directly.
+        loop {
+            let aliases: Vec<(String, (String, usize))> = declarations
+                .iter()
+                .filter(|decl| !uncurried.contains_key(&decl.id))
+                .filter_map(|decl| match &decl.expr {
+                    Expr::ID(target, _) => uncurried
+                        .get(target)
+                        .map(|entry| (decl.id.clone(), entry.clone())),
+                    _ => None,
+                })
+                .collect();
+            if aliases.is_empty() {
+                break;
+            }
+            uncurried.extend(aliases);
+        }
+        let mut cafs: HashSet<String> = declarations
+            .iter()
+            .filter(|decl| matches!(decl.expr, Expr::Call(_, _, _)))
+            .map(|decl| decl.id.clone())
+            .collect();
+        loop {
+            let aliases: Vec<String> = declarations
+                .iter()
+                .filter(|decl| !cafs.contains(&decl.id))
+                .filter(|decl| match &decl.expr {
+                    Expr::ID(target, _) => cafs.contains(target),
+                    _ => false,
+                })
+                .map(|decl| decl.id.clone())
+                .collect();
+            if aliases.is_empty() {
+                break;
             }
-            other => Self::from(other).constt(&decl.id),
+            cafs.extend(aliases);
+        }
+        let lazy = stdlib::lazy_params()
+            .into_iter()
+            .map(|(id, params)| (uncurried[&id].0.clone(), params))
+            .collect();
+        Self {
+            uncurried,
+            lazy,
+            cafs,
+            trampoline,
         }
     }
-}
 
-fn reduce_func(ps: Vec<String>, expr: Expr) -> Expr {
-    if ps.len() == 1 {
-        expr
-    } else {
-        Expr::Func(ps.split_at(1).1.to_vec().into(), expr.into())
+    /// Parts of the std runtime that the generated code uses besides the
+    /// builtins the program refers to.
+    fn runtime(&self) -> Vec<String> {
+        let caf = Some("caf").filter(|_| !self.cafs.is_empty());
+        let trampoline =
+            ["bounce", "land"].into_iter().filter(|_| self.trampoline);
+        caf.into_iter()
+            .chain(trampoline)
+            .map(String::from)
+            .collect()
     }
-}
 
-impl From<Vec<Declaration>> for JS {
-    fn from(decls: Vec<Declaration>) -> Self {
-        Self::Proc(decls.into_iter().map(Self::from).collect())
+    /// Declarations in the order they are emitted: as given, except that an
+    /// alias that is not a cell comes after the declaration it refers to,
+    /// which it reads when the module loads. Functions and cells only read
+    /// other declarations once the program runs, when all of them are defined.
+    fn ordered(&self, declarations: Vec<Declaration>) -> Vec<Declaration> {
+        let by_id: HashMap<String, Declaration> = declarations
+            .iter()
+            .map(|decl| (decl.id.clone(), decl.clone()))
+            .collect();
+        let mut visited = HashSet::new();
+        let mut ordered = vec![];
+        for decl in declarations {
+            self.visit(decl, &by_id, &mut visited, &mut ordered);
+        }
+        ordered
     }
-}
 
-impl From<Expr> for JS {
-    fn from(expr: Expr) -> Self {
+    fn visit(
+        &self,
+        decl: Declaration,
+        by_id: &HashMap<String, Declaration>,
+        visited: &mut HashSet<String>,
+        ordered: &mut Vec<Declaration>,
+    ) {
+        if !visited.insert(decl.id.clone()) {
+            return;
+        }
+        // Constants are integers or aliases, and cells and functions don't
+        // need anything defined before them.
+        if let Expr::ID(target, _) = &decl.expr {
+            if let Some(target) = by_id.get(target) {
+                if !self.cafs.contains(&decl.id) {
+                    self.visit(target.clone(), by_id, visited, ordered);
+                }
+            }
+        }
+        ordered.push(decl);
+    }
+
+    /// Functions with an uncurried entry point are emitted twice: uncurried,
+    /// and as the curried function that other code sees, which defers to it.
+    fn declaration(&self, decl: Declaration) -> Vec<JS> {
+        let name = mangle(&decl.id);
+        let js = match decl.expr {
+            Expr::Func(ps, expr, _) => {
+                let params: Vec<String> =
+                    ps.iter().map(|param| mangle(param)).collect();
+                match self.uncurried.get(&decl.id) {
+                    Some((uncurried, _)) => {
+                        let call = self.land(
+                            &decl.id,
+                            JS::name(uncurried).apply(
+                                params.iter().map(|p| JS::name(p)).collect(),
+                            ),
+                        );
+                        let curried = if params.len() > 1 {
+                            JS::Func(params[1..].to_vec(), call.into())
+                        } else {
+                            call
+                        };
+                        vec![
+                            self.function(&decl.id, uncurried, &params, *expr),
+                            JS::Proc(vec![curried.returns()])
+                                .function(&name, vec![&params[0]]),
+                        ]
+                    }
+                    None => {
+                        vec![self.function(&decl.id, &name, &params, *expr)]
+                    }
+                }
+            }
+            expr if self.cafs.contains(&decl.id) => {
+                vec![JS::name("std.caf")
+                    .call(vec![JS::Func(vec![], self.expr(expr).into())])
+                    .constt(&name)]
+            }
+            other => vec![self.expr(other).constt(&name)],
+        };
+        js.into_iter()
+            .map(|js| JS::Mapped(decl.span, js.into()))
+            .collect()
+    }
+
+    /// A function that calls itself in tail position becomes a loop: the
+    /// parameters are variables that each self call reassigns before it
+    /// jumps back to the start, where they are copied to constants, so that
+    /// closures created in one iteration don't see the next one's values.
+    fn function(
+        &self,
+        id: &str,
+        name: &str,
+        params: &[String],
+        body: Expr,
+    ) -> JS {
+        let statements = self.tail(id, params, body.clone());
+        if statements.iter().any(JS::continues) {
+            let vars: Vec<String> =
+                params.iter().map(|param| loop_var(param)).collect();
+            let copies = params
+                .iter()
+                .zip(&vars)
+                .map(|(param, var)| JS::name(var).constt(param));
+            JS::Proc(vec![JS::Loop(
+                JS::Proc(copies.chain(statements).collect()).into(),
+            )])
+            .function(name, vars.iter().map(String::as_str).collect())
+        } else if self.bounces(id) {
+            JS::Proc(statements)
+                .function(name, params.iter().map(String::as_str).collect())
+        } else {
+            JS::Proc(vec![self.expr(body).returns()])
+                .function(name, params.iter().map(String::as_str).collect())
+        }
+    }
+
+    /// Statements that return the value of `expr`, found in tail position of
+    /// the function `id`. Both arms of a saturated `std.iff` are in tail
+    /// position too, though only the one chosen is evaluated.
+    fn tail(&self, id: &str, params: &[String], expr: Expr) -> Vec<JS> {
         match expr {
-            Expr::Int(i) => Self::Int(i),
-            Expr::Name(name) | Expr::ID(name) => Self::Name(name),
-            Expr::Call(f, args) => Self::Call(
-                Box::new(Self::from(*f)),
-                args.into_iter().map(Self::from).collect(),
-            ),
-            Expr::Func(params, expr) => {
-                Self::Func(*params, Box::new(Self::from(*expr)))
+            Expr::Call(f, args, span) => {
+                self.tail_call(id, params, *f, args, span)
             }
+            other => vec![self.expr(other).returns()],
         }
     }
-}
 
-impl Into<String> for JS {
-    fn into(self) -> String {
-        match self {
-            Self::Call(f, args) => {
-                let args: Vec<String> = args
-                    .into_iter()
-                    .map(|arg| {
-                        let s: String = arg.into();
-                        format!("({})", s)
-                    })
-                    .collect();
-                let fargs = if args.is_empty() {
-                    "()".into()
-                } else {
-                    args.join(" ")
-                };
-                format!("{} {}", f.bracketed_func(), fargs)
+    fn tail_call(
+        &self,
+        id: &str,
+        params: &[String],
+        f: Expr,
+        mut args: Vec<Expr>,
+        span: Span,
+    ) -> Vec<JS> {
+        let statements = match f {
+            Expr::Call(g, mut first, _) => {
+                first.append(&mut args);
+                return self.tail_call(id, params, *g, first, span);
             }
-            Self::Func(params, body) => {
-                let body: String = (*body).into();
-                format!("{} => {}", params.join(" => "), body)
-            }
-            Self::Name(name) => name,
-            Self::Int(i) => i.to_string(),
-            Self::Assign(left, expr) => {
-                let s: String = (*expr).into();
-                format!("{} = {};", left, s)
-            }
-            Self::Kw(kw, expr) => {
-                let s: String = (*expr).into();
-                format!("{} {}", kw, s)
-            }
-            Self::Function(name, args, expr) => match *expr {
-                Self::Proc(ss) => format!(
-                    "function {}({}) {}",
-                    name,
-                    args.join(", "),
-                    <Self as Into<String>>::into(Self::Proc(ss))
-                ),
-                other => panic!("Unexpected function body {:?}", other),
-            },
-            Self::Proc(statements) => {
-                let ss: Vec<String> =
-                    statements.into_iter().map(|s| s.into()).collect();
-                format!("{{ {} }}", ss.join(" "))
-            }
-            Self::Object(map) => {
-                let pairs: Vec<String> = map
-                    .into_iter()
-                    .map(|(key, expr)| {
-                        format!(
-                            "{}: {}",
-                            key,
-                            <Self as Into<String>>::into(expr)
-                        )
-                    })
-                    .collect();
-                format!("{{{}}}", pairs.join(","))
+            Expr::ID(callee, _)
+                if callee == id && args.len() == params.len() =>
+            {
+                params
+                    .iter()
+                    .zip(args)
+                    .map(|(param, arg)| self.expr(arg).assign(&loop_var(param)))
+                    .chain([JS::Continue])
+                    .collect()
+            }
+            Expr::ID(callee, _) if self.is_iff(&callee) && args.len() >= 3 => {
+                let rest = args.split_off(3);
+                let mut args = args.into_iter();
+                let cond = self.expr(args.next().unwrap());
+                let mut arms = args.map(|arm| {
+                    let arm = if rest.is_empty() {
+                        arm
+                    } else {
+                        let span = arm.span();
+                        Expr::Call(arm.into(), rest.clone(), span)
+                    };
+                    JS::Proc(self.tail(id, params, arm))
+                });
+                let then = arms.next().unwrap();
+                let otherwise = arms.next().unwrap();
+                vec![JS::If(cond.into(), then.into(), otherwise.into())]
+            }
+            Expr::ID(callee, callee_span)
+                if self.bounces(id)
+                    && self.bounces(&callee)
+                    && args.len() == self.uncurried[&callee].1 =>
+            {
+                let (uncurried, _) = &self.uncurried[&callee];
+                let call = JS::Mapped(callee_span, JS::name(uncurried).into())
+                    .apply(self.exprs(args));
+                vec![JS::name("std.bounce")
+                    .apply(vec![JS::Func(vec![], call.into())])
+                    .returns()]
             }
-            Self::Str(s) => format!(r#""{}""#, s),
+            f => vec![self.call(f, args).returns()],
+        };
+        let mut statements = statements.into_iter();
+        let first = JS::Mapped(span, statements.next().unwrap().into());
+        std::iter::once(first).chain(statements).collect()
+    }
+
+    fn is_iff(&self, id: &str) -> bool {
+        self.uncurried
+            .get(id)
+            .is_some_and(|(name, _)| *name == uncurried_name("std.iff", 3))
+    }
+
+    /// Whether calls to `id` may return a bounce rather than a value.
+    fn bounces(&self, id: &str) -> bool {
+        self.trampoline
+            && self
+                .uncurried
+                .get(id)
+                .is_some_and(|(name, _)| !name.starts_with("std."))
+    }
+
+    /// Runs a call to `id` that may bounce until it returns a value.
+    fn land(&self, id: &str, call: JS) -> JS {
+        if self.bounces(id) {
+            JS::name("std.land").apply(vec![call])
+        } else {
+            call
         }
     }
-}
 
-impl JS {
-    fn bracketed_func(self) -> String {
-        match self {
-            Self::Func(params, body) => {
-                let s: String = Self::Func(params, body).into();
-                format!("({})", s)
+    fn expr(&self, expr: Expr) -> JS {
+        let span = expr.span();
+        let js = match expr {
+            Expr::Int(i, _) => JS::Int(i),
+            Expr::ID(id, _) if self.cafs.contains(&id) => {
+                JS::Name(mangle(&id)).call(vec![])
             }
-            other => other.into(),
+            Expr::Name(name, _) | Expr::ID(name, _) => JS::Name(mangle(&name)),
+            Expr::Call(f, args, _) => self.call(*f, args),
+            Expr::Func(params, expr, _) => JS::Func(
+                params.iter().map(|param| mangle(param)).collect(),
+                self.expr(*expr).into(),
+            ),
+        };
+        JS::Mapped(span, js.into())
+    }
+
+    /// Calls with at least as many arguments as the uncurried entry point of
+    /// the function takes go through it, and any further arguments are then
+    /// applied one at a time. Saturated `std.iff` becomes a conditional
+    /// expression, which only evaluates the arm it picks.
+    fn call(&self, f: Expr, mut args: Vec<Expr>) -> JS {
+        match f {
+            Expr::Call(g, mut first, _) => {
+                first.append(&mut args);
+                self.call(*g, first)
+            }
+            Expr::ID(ref id, span) => match self.uncurried.get(id) {
+                Some((uncurried, arity)) if args.len() >= *arity => {
+                    let rest = args.split_off(*arity);
+                    let call = if self.is_iff(id) {
+                        let mut args = self.exprs(args).into_iter();
+                        let mut next = || args.next().unwrap().into();
+                        JS::Cond(next(), next(), next())
+                    } else {
+                        let lazy = self.lazy.get(uncurried);
+                        let args = args
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, arg)| match lazy {
+                                Some(lazy) if lazy.contains(&i) => {
+                                    JS::Func(vec![], self.expr(arg).into())
+                                }
+                                _ => self.expr(arg),
+                            })
+                            .collect();
+                        let call = JS::Mapped(span, JS::name(uncurried).into())
+                            .apply(args);
+                        self.land(id, call)
+                    };
+                    if rest.is_empty() {
+                        call
+                    } else {
+                        call.call(self.exprs(rest))
+                    }
+                }
+                _ => self.expr(f).call(self.exprs(args)),
+            },
+            f => self.expr(f).call(self.exprs(args)),
         }
     }
+
+    fn exprs(&self, exprs: Vec<Expr>) -> Vec<JS> {
+        exprs.into_iter().map(|expr| self.expr(expr)).collect()
+    }
+}
+
+fn uncurried_name(id: &str, arity: usize) -> String {
+    format!("{}${}", mangle(id), arity)
+}
+
+/// The variable that holds a parameter of a looping function between
+/// iterations.
+fn loop_var(param: &str) -> String {
+    format!("{}$", param)
+}
+
+/// Words that a Pure name must not be emitted as verbatim: JavaScript
+/// keywords, literals and the bindings the generated module relies on.
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "exports",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "Infinity",
+    "instanceof",
+    "interface",
+    "let",
+    "module",
+    "NaN",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "require",
+    "return",
+    "static",
+    "std",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Turns a Pure name or id into a valid JavaScript identifier.
+///
+/// References to `std` builtins are left intact so that they resolve against
+/// the runtime module. Everything else has its dots replaced with `$`, and
+/// reserved words get a trailing `$`. Operators are spelled out character by
+/// character after a leading `$`, e.g. `<*>` becomes `$lt$star$gt`. Pure names
+/// cannot contain `$`, so the mangled names never collide with each other.
+fn mangle(id: &str) -> String {
+    if stdlib::index().contains_key(id) {
+        id.to_string()
+    } else if let Some(chars) =
+        id.chars().map(operator_char).collect::<Option<Vec<_>>>()
+    {
+        format!("${}", chars.join("$"))
+    } else if RESERVED.contains(&id) {
+        format!("{}$", id)
+    } else {
+        id.replace('.', "$")
+    }
+}
+
+fn operator_char(c: char) -> Option<&'static str> {
+    Some(match c {
+        '<' => "lt",
+        '>' => "gt",
+        '*' => "star",
+        '/' => "slash",
+        '|' => "bar",
+        '!' => "bang",
+        '@' => "at",
+        '#' => "hash",
+        '$' => "dollar",
+        '%' => "percent",
+        '^' => "caret",
+        '&' => "amp",
+        '-' => "minus",
+        '+' => "plus",
+        '=' => "eq",
+        _ => return None,
+    })
 }
@@ -0,0 +1,233 @@
+use super::{JS, RESERVED};
+use std::collections::HashSet;
+
+/// Renames function and lambda parameters to the shortest names available.
+///
+/// Parameters along one chain of nested functions all get distinct names, so
+/// nothing is captured by mistake, while sibling functions reuse the same
+/// ones. Names that occur anywhere in the program are never picked, which
+/// keeps references to globals intact.
+pub fn shorten_locals(statements: Vec<JS>) -> Vec<JS> {
+    let mut taken: HashSet<String> =
+        RESERVED.iter().map(|word| word.to_string()).collect();
+    for js in statements.iter() {
+        collect_names(js, &mut taken);
+    }
+    let mut renamer = Renamer {
+        taken,
+        scope: vec![],
+    };
+    statements
+        .into_iter()
+        .map(|js| renamer.rename(js))
+        .collect()
+}
+
+fn collect_names(js: &JS, names: &mut HashSet<String>) {
+    match js {
+        JS::Name(name) => {
+            names.insert(name.clone());
+        }
+        JS::Assign(left, expr) => {
+            names.extend(left.split_whitespace().map(String::from));
+            collect_names(expr, names);
+        }
+        JS::Function(name, _, body) => {
+            names.insert(name.clone());
+            collect_names(body, names);
+        }
+        JS::Call(f, args) | JS::Apply(f, args) => {
+            collect_names(f, names);
+            args.iter().for_each(|arg| collect_names(arg, names));
+        }
+        JS::Kw(_, js)
+        | JS::Func(_, js)
+        | JS::Mapped(_, js)
+        | JS::Getter(js) => collect_names(js, names),
+        JS::Proc(statements) => {
+            statements.iter().for_each(|js| collect_names(js, names))
+        }
+        JS::If(cond, then, otherwise) | JS::Cond(cond, then, otherwise) => {
+            collect_names(cond, names);
+            collect_names(then, names);
+            collect_names(otherwise, names);
+        }
+        JS::Loop(body) => collect_names(body, names),
+        JS::Object(pairs) => {
+            pairs.iter().for_each(|(_, js)| collect_names(js, names))
+        }
+        JS::Import(name, _) => {
+            names.insert(name.clone());
+        }
+        JS::Export(pairs) => {
+            names.extend(pairs.iter().map(|(local, _)| local.clone()))
+        }
+        JS::Raw(_) | JS::Str(_) | JS::Int(_) | JS::Continue => {}
+    }
+}
+
+struct Renamer {
+    taken: HashSet<String>,
+    /// Parameters in scope, innermost last, with their new names.
+    scope: Vec<(String, String)>,
+}
+
+impl Renamer {
+    fn rename(&mut self, js: JS) -> JS {
+        match js {
+            JS::Name(name) => JS::Name(self.lookup(name)),
+            JS::Function(name, params, body) => {
+                let depth = self.scope.len();
+                let params = self.bind(params);
+                let body = self.rename(*body);
+                self.scope.truncate(depth);
+                JS::Function(name, params, body.into())
+            }
+            JS::Func(params, body) => {
+                let depth = self.scope.len();
+                let params = self.bind(params);
+                let body = self.rename(*body);
+                self.scope.truncate(depth);
+                JS::Func(params, body.into())
+            }
+            // Constants declared within functions, the copies of loop
+            // variables such as `const n = n$;`, are locals too. Outside of
+            // functions they are globals, and keep their names.
+            JS::Assign(left, expr) => {
+                let expr = self.rename(*expr).into();
+                match left.split_once(' ') {
+                    Some((kw, name)) if !self.scope.is_empty() => {
+                        let short = self.bind(vec![name.to_string()]).remove(0);
+                        JS::Assign(format!("{} {}", kw, short), expr)
+                    }
+                    // Reassigned parameters, e.g. `n$ = n - 1;`, are renamed
+                    // too.
+                    _ => JS::Assign(self.lookup(left), expr),
+                }
+            }
+            JS::Call(f, args) => JS::Call(
+                self.rename(*f).into(),
+                args.into_iter().map(|arg| self.rename(arg)).collect(),
+            ),
+            JS::Apply(f, args) => JS::Apply(
+                self.rename(*f).into(),
+                args.into_iter().map(|arg| self.rename(arg)).collect(),
+            ),
+            JS::If(cond, then, otherwise) => JS::If(
+                self.rename(*cond).into(),
+                self.rename(*then).into(),
+                self.rename(*otherwise).into(),
+            ),
+            JS::Cond(cond, then, otherwise) => JS::Cond(
+                self.rename(*cond).into(),
+                self.rename(*then).into(),
+                self.rename(*otherwise).into(),
+            ),
+            JS::Loop(body) => JS::Loop(self.rename(*body).into()),
+            JS::Getter(body) => JS::Getter(self.rename(*body).into()),
+            JS::Kw(kw, js) => JS::Kw(kw, self.rename(*js).into()),
+            JS::Mapped(span, js) => JS::Mapped(span, self.rename(*js).into()),
+            JS::Proc(statements) => JS::Proc(
+                statements.into_iter().map(|js| self.rename(js)).collect(),
+            ),
+            JS::Object(pairs) => JS::Object(
+                pairs
+                    .into_iter()
+                    .map(|(key, js)| (key, self.rename(js)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn lookup(&self, name: String) -> String {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(param, _)| *param == name)
+            .map(|(_, short)| short.clone())
+            .unwrap_or(name)
+    }
+
+    fn bind(&mut self, params: Vec<String>) -> Vec<String> {
+        params
+            .into_iter()
+            .map(|param| {
+                let short = self.fresh();
+                self.scope.push((param, short.clone()));
+                short
+            })
+            .collect()
+    }
+
+    fn fresh(&self) -> String {
+        (0..)
+            .map(short_name)
+            .find(|name| {
+                !self.taken.contains(name)
+                    && !self.scope.iter().any(|(_, short)| short == name)
+            })
+            .unwrap()
+    }
+}
+
+/// `a` to `z`, then `A` to `Z`, then `aa`, `ab` and so on.
+fn short_name(mut n: usize) -> String {
+    const LETTERS: &[u8] =
+        b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut name = vec![];
+    loop {
+        name.push(LETTERS[n % LETTERS.len()]);
+        if n < LETTERS.len() {
+            break;
+        }
+        n = n / LETTERS.len() - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+/// Leaves out the whitespace of hand-written code, such as the bundled
+/// runtime, that doesn't separate two tokens. Line breaks go too, so the code
+/// must end its statements with semicolons. String and template literals are
+/// copied as they are.
+pub fn raw(code: &str) -> String {
+    let mut minified = String::new();
+    let mut chars = code.chars();
+    let mut space = false;
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space && needs_space(minified.chars().last(), c) {
+            minified.push(' ');
+        }
+        space = false;
+        minified.push(c);
+        if matches!(c, '"' | '\'' | '`') {
+            while let Some(next) = chars.next() {
+                minified.push(next);
+                if next == '\\' {
+                    minified.extend(chars.next());
+                } else if next == c {
+                    break;
+                }
+            }
+        }
+    }
+    minified
+}
+
+/// Whether leaving out the space between `before` and `after` would join two
+/// tokens into one, e.g. `return x` or `a - -b`.
+fn needs_space(before: Option<char>, after: char) -> bool {
+    let word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    match before {
+        Some(before) => {
+            (word(before) && word(after))
+                || (before == after && matches!(after, '+' | '-'))
+        }
+        None => false,
+    }
+}
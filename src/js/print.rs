@@ -0,0 +1,473 @@
+use super::JS;
+use crate::ast::Position;
+
+const WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+/// A point in the generated code that corresponds to a point in the source.
+/// Generated lines and columns start at 0, as they do in source maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub line: usize,
+    pub column: usize,
+    pub source: Position,
+}
+
+/// Layout of the code before it is fitted to the line width.
+#[derive(Clone, Debug)]
+enum Doc {
+    Text(String),
+    /// A space, dropped when minified.
+    Space,
+    /// A space, or a line break if the enclosing group doesn't fit.
+    Line,
+    /// Nothing, or a line break if the enclosing group doesn't fit.
+    SoftLine,
+    /// Always a line break, unless minified.
+    HardLine,
+    /// Indents the line breaks within.
+    Nest(Box<Doc>),
+    /// Laid out on a single line if it fits, otherwise its lines break.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+    /// Records a mapping to the source at this point.
+    Mark(Position),
+}
+
+fn text(s: &str) -> Doc {
+    Doc::Text(s.to_string())
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(doc.into())
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(doc.into())
+}
+
+fn join(docs: Vec<Doc>, separator: Doc) -> Doc {
+    let mut joined = vec![];
+    for (i, doc) in docs.into_iter().enumerate() {
+        if i > 0 {
+            joined.push(separator.clone());
+        }
+        joined.push(doc);
+    }
+    concat(joined)
+}
+
+/// Operator precedence of an expression, as in the table of the JavaScript
+/// operators. Sub-expressions are bracketed only when they bind looser than
+/// their position requires.
+fn precedence(js: &JS) -> u8 {
+    match js {
+        JS::Mapped(_, js) => precedence(js),
+        JS::Func(_, _) | JS::Cond(_, _, _) => 2,
+        JS::Kw(_, _) => 14,
+        JS::Int(i) if *i < 0 => 14,
+        JS::Call(_, _) | JS::Apply(_, _) => 17,
+        _ => 20,
+    }
+}
+
+const CALL: u8 = 17;
+
+/// Renders statements either readably, indented and wrapped at 80 columns,
+/// or minified, with all optional whitespace left out.
+pub fn print(statements: Vec<JS>, minify: bool) -> (String, Vec<Mapping>) {
+    let separator = concat(vec![Doc::HardLine, Doc::HardLine]);
+    let doc = join(statements.into_iter().map(statement).collect(), separator);
+    let mut renderer = Renderer {
+        minify,
+        ..Renderer::default()
+    };
+    renderer.render(&doc);
+    renderer.write("\n");
+    (renderer.code, renderer.mappings)
+}
+
+fn statement(js: JS) -> Doc {
+    match js {
+        JS::Mapped(span, js) => {
+            concat(vec![Doc::Mark(span.start), statement(*js)])
+        }
+        // Objects, functions and calls break within themselves, the rest
+        // move to the next line when they don't fit.
+        JS::Assign(left, expr) if expr.breaks_itself() => concat(vec![
+            text(&left),
+            Doc::Space,
+            text("="),
+            Doc::Space,
+            expression(*expr, 0),
+            text(";"),
+        ]),
+        JS::Assign(left, expr) => group(concat(vec![
+            text(&left),
+            Doc::Space,
+            text("="),
+            nest(concat(vec![Doc::Line, expression(*expr, 0)])),
+            text(";"),
+        ])),
+        JS::Function(name, params, body) => concat(vec![
+            text(&format!("function {}(", name)),
+            join(
+                params.iter().map(|param| text(param)).collect(),
+                concat(vec![text(","), Doc::Space]),
+            ),
+            text(")"),
+            Doc::Space,
+            block(*body),
+        ]),
+        JS::Kw(kw, expr) => concat(vec![
+            text(&format!("{} ", kw)),
+            expression(*expr, 0),
+            text(";"),
+        ]),
+        JS::Import(name, path) => {
+            text(&format!(r#"import * as {} from "{}";"#, name, path))
+        }
+        JS::Export(names) => group(concat(vec![
+            text("export"),
+            Doc::Space,
+            text("{"),
+            nest(concat(vec![
+                Doc::Line,
+                join(
+                    names
+                        .into_iter()
+                        .map(|(local, name)| {
+                            if local == name {
+                                text(&local)
+                            } else {
+                                text(&format!("{} as {}", local, name))
+                            }
+                        })
+                        .collect(),
+                    concat(vec![text(","), Doc::Line]),
+                ),
+            ])),
+            Doc::Line,
+            text("};"),
+        ])),
+        JS::If(cond, then, otherwise) => concat(vec![
+            text("if"),
+            Doc::Space,
+            text("("),
+            expression(*cond, 0),
+            text(")"),
+            Doc::Space,
+            block(*then),
+            Doc::Space,
+            match *otherwise {
+                JS::Proc(mut statements)
+                    if statements.len() == 1 && statements[0].is_if() =>
+                {
+                    concat(vec![text("else "), statement(statements.remove(0))])
+                }
+                otherwise => {
+                    concat(vec![text("else"), Doc::Space, block(otherwise)])
+                }
+            },
+        ]),
+        JS::Loop(body) => concat(vec![
+            text("for"),
+            Doc::Space,
+            text("(;;)"),
+            Doc::Space,
+            block(*body),
+        ]),
+        JS::Continue => text("continue;"),
+        JS::Raw(code) => text(&code),
+        JS::Proc(_) => block(js),
+        expr => concat(vec![expression(expr, 0), text(";")]),
+    }
+}
+
+fn block(js: JS) -> Doc {
+    match js {
+        JS::Proc(statements) if statements.is_empty() => text("{}"),
+        JS::Proc(statements) => concat(vec![
+            text("{"),
+            nest(concat(
+                statements
+                    .into_iter()
+                    .flat_map(|js| vec![Doc::HardLine, statement(js)])
+                    .collect(),
+            )),
+            Doc::HardLine,
+            text("}"),
+        ]),
+        other => panic!("Unexpected block {:?}", other),
+    }
+}
+
+/// Lays out an expression in a position that requires at least the given
+/// precedence.
+fn expression(js: JS, min_precedence: u8) -> Doc {
+    if precedence(&js) < min_precedence {
+        return concat(vec![text("("), expression(js, 0), text(")")]);
+    }
+    match js {
+        JS::Mapped(span, js) => {
+            concat(vec![Doc::Mark(span.start), expression(*js, min_precedence)])
+        }
+        // Curried: each argument is applied on its own, `f(x)(y)(z)`.
+        JS::Call(f, args) if args.is_empty() => {
+            concat(vec![expression(*f, CALL), text("()")])
+        }
+        JS::Call(f, args) => concat(
+            std::iter::once(expression(*f, CALL))
+                .chain(args.into_iter().map(|arg| {
+                    group(concat(vec![
+                        text("("),
+                        nest(concat(vec![Doc::SoftLine, expression(arg, 2)])),
+                        Doc::SoftLine,
+                        text(")"),
+                    ]))
+                }))
+                .collect(),
+        ),
+        JS::Func(params, body) if params.is_empty() => group(concat(vec![
+            text("()"),
+            Doc::Space,
+            text("=>"),
+            Doc::Space,
+            expression(*body, 2),
+        ])),
+        JS::Func(params, body) => {
+            let arrows: Vec<Doc> = params
+                .into_iter()
+                .map(|param| {
+                    concat(vec![
+                        text(&param),
+                        Doc::Space,
+                        text("=>"),
+                        Doc::Space,
+                    ])
+                })
+                .collect();
+            let body = match *body {
+                JS::Object(pairs) => concat(vec![
+                    text("("),
+                    expression(JS::Object(pairs), 0),
+                    text(")"),
+                ]),
+                body => expression(body, 2),
+            };
+            group(concat(vec![concat(arrows), body]))
+        }
+        JS::Cond(cond, then, otherwise) => group(concat(vec![
+            expression(*cond, 3),
+            nest(concat(vec![
+                Doc::Line,
+                text("?"),
+                Doc::Space,
+                expression(*then, 2),
+                Doc::Line,
+                text(":"),
+                Doc::Space,
+                expression(*otherwise, 2),
+            ])),
+        ])),
+        JS::Kw(kw, expr) => {
+            concat(vec![text(&format!("{} ", kw)), expression(*expr, 14)])
+        }
+        JS::Object(pairs) if pairs.is_empty() => text("{}"),
+        JS::Object(pairs) => group(concat(vec![
+            text("{"),
+            nest(concat(vec![
+                Doc::Line,
+                join(
+                    pairs
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let key = if key.contains('.') {
+                                format!(r#""{}""#, key)
+                            } else {
+                                key
+                            };
+                            match value {
+                                JS::Getter(body) => concat(vec![
+                                    text(&format!("get {}()", key)),
+                                    Doc::Space,
+                                    text("{"),
+                                    Doc::Space,
+                                    text("return "),
+                                    expression(*body, 0),
+                                    text(";"),
+                                    Doc::Space,
+                                    text("}"),
+                                ]),
+                                value => concat(vec![
+                                    text(&key),
+                                    text(":"),
+                                    Doc::Space,
+                                    expression(value, 2),
+                                ]),
+                            }
+                        })
+                        .collect(),
+                    concat(vec![text(","), Doc::Line]),
+                ),
+            ])),
+            Doc::Line,
+            text("}"),
+        ])),
+        JS::Apply(f, args) => concat(vec![
+            expression(*f, CALL),
+            group(concat(vec![
+                text("("),
+                nest(concat(vec![
+                    Doc::SoftLine,
+                    join(
+                        args.into_iter()
+                            .map(|arg| expression(arg, 2))
+                            .collect(),
+                        concat(vec![text(","), Doc::Line]),
+                    ),
+                ])),
+                Doc::SoftLine,
+                text(")"),
+            ])),
+        ]),
+        JS::Name(name) => text(&name),
+        JS::Int(i) => text(&i.to_string()),
+        JS::Str(s) => text(&format!(r#""{}""#, s)),
+        JS::Raw(code) => text(&code),
+        other => panic!("Unexpected expression {:?}", other),
+    }
+}
+
+#[derive(Default)]
+struct Renderer {
+    minify: bool,
+    code: String,
+    mappings: Vec<Mapping>,
+    line: usize,
+    column: usize,
+}
+
+impl Renderer {
+    fn render(&mut self, doc: &Doc) {
+        let mut stack = vec![(0, false, doc)];
+        while let Some((indent, flat, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(s) => self.write(s),
+                Doc::Space => self.space(),
+                Doc::Line if flat => self.space(),
+                Doc::Line | Doc::SoftLine | Doc::HardLine if self.minify => {}
+                Doc::SoftLine if flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    self.newline(indent)
+                }
+                Doc::Nest(doc) => stack.push((indent + INDENT, flat, doc)),
+                Doc::Group(doc) => {
+                    let flat = flat
+                        || self.minify
+                        || self.fits(
+                            WIDTH.saturating_sub(self.column),
+                            doc,
+                            &stack,
+                        );
+                    stack.push((indent, flat, doc));
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|d| (indent, flat, d)))
+                }
+                Doc::Mark(source) => self.mappings.push(Mapping {
+                    line: self.line,
+                    column: self.column,
+                    source: *source,
+                }),
+            }
+        }
+    }
+
+    /// Whether the group fits on the rest of the line when laid out flat,
+    /// along with whatever follows it up to the next line break.
+    fn fits(
+        &self,
+        width: usize,
+        group: &Doc,
+        rest: &[(usize, bool, &Doc)],
+    ) -> bool {
+        let mut width = width as isize;
+        let mut stack: Vec<(bool, &Doc)> = vec![(true, group)];
+        let mut rest = rest.iter().rev();
+        loop {
+            let (flat, doc) = match stack.pop() {
+                Some(item) => item,
+                None => match rest.next() {
+                    Some((_, flat, doc)) => (*flat, *doc),
+                    None => return true,
+                },
+            };
+            match doc {
+                Doc::Text(s) => width -= s.chars().count() as isize,
+                Doc::Space => width -= 1,
+                Doc::Line if flat => width -= 1,
+                Doc::SoftLine if flat => {}
+                Doc::HardLine if flat => return false,
+                Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+                Doc::Nest(doc) => stack.push((flat, doc)),
+                Doc::Group(doc) => stack.push((flat, doc)),
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|d| (flat, d)))
+                }
+                Doc::Mark(_) => {}
+            }
+            if width < 0 {
+                return false;
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        if !self.minify {
+            self.write(" ");
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.write("\n");
+        self.write(&" ".repeat(indent));
+    }
+
+    fn write(&mut self, s: &str) {
+        for c in s.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 0;
+            } else {
+                self.column += c.len_utf16();
+            }
+        }
+        self.code.push_str(s);
+    }
+}
+
+impl JS {
+    fn breaks_itself(&self) -> bool {
+        match self {
+            Self::Func(_, _)
+            | Self::Object(_)
+            | Self::Call(_, _)
+            | Self::Apply(_, _) => true,
+            Self::Mapped(_, js) => js.breaks_itself(),
+            _ => false,
+        }
+    }
+
+    fn is_if(&self) -> bool {
+        match self {
+            Self::If(_, _, _) => true,
+            Self::Mapped(_, js) => js.is_if(),
+            _ => false,
+        }
+    }
+}
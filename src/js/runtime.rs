@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+
+use super::Module;
+use crate::stdlib::{Builtin, BUILTINS};
+
+/// Parts of the runtime that generated code relies on besides the builtins,
+/// as `(name, comment, source)`.
+const SUPPORT: &[(&str, &str, &str)] = &[
+    (
+        "Panic",
+        "Runtime errors: a partial builtin applied outside its domain panics, naming
+itself. A program that panics reports it on stderr and exits with 101.",
+        "class extends Error {
+  constructor(builtin, message) {
+    super(`panic in ${builtin}: ${message}`);
+    this.name = \"Panic\";
+    this.builtin = builtin;
+  }
+}",
+    ),
+    (
+        "panic",
+        "",
+        "(builtin, message) => {
+  throw new Panic(builtin, message);
+}",
+    ),
+    (
+        "run",
+        "run :: Va. (() -> Cmd a) -> a",
+        "(main) => {
+  try {
+    return main()();
+  } catch (error) {
+    if (!(error instanceof Panic)) {
+      throw error;
+    }
+    console.error(error.message);
+    process.exit(101);
+  }
+}",
+    ),
+    (
+        "Bounce",
+        "Trampoline for --trampoline: a tail call between functions returns a
+Bounce, which the nearest caller not in tail position runs to completion.",
+        "class {
+  constructor(next) {
+    this.next = next;
+  }
+}",
+    ),
+    ("bounce", "", "(next) => new Bounce(next)"),
+    (
+        "land",
+        "",
+        "(result) => {
+  while (result instanceof Bounce) {
+    result = result.next();
+  }
+  return result;
+}",
+    ),
+    (
+        "caf",
+        "Constants are cells that compute their value the first time they are
+used, and only then.",
+        "(compute) => {
+  let evaluated = false;
+  let value;
+  return () => {
+    if (!evaluated) {
+      value = compute();
+      evaluated = true;
+    }
+    return value;
+  };
+}",
+    ),
+];
+
+/// A top-level definition of the runtime: `const name = source;`.
+pub struct Definition {
+    pub name: String,
+    /// Comment that goes above the definition, if any.
+    pub comment: String,
+    pub source: String,
+}
+
+/// Definitions of the runtime, in the order they appear in std.js: the
+/// support code first, then each builtin, with the uncurried entry point of
+/// functions of several parameters before the curried version.
+pub fn definitions() -> Vec<Definition> {
+    let support = SUPPORT.iter().map(|(name, comment, source)| Definition {
+        name: name.to_string(),
+        comment: comment.to_string(),
+        source: source.to_string(),
+    });
+    let builtins = BUILTINS.iter().flat_map(|builtin| {
+        let comment = format!("{} :: {}", builtin.name, builtin.ty);
+        if builtin.arity > 1 {
+            vec![
+                Definition {
+                    name: uncurried(builtin),
+                    comment,
+                    source: builtin.js.to_string(),
+                },
+                Definition {
+                    name: builtin.name.to_string(),
+                    comment: String::new(),
+                    source: curried(builtin),
+                },
+            ]
+        } else {
+            vec![Definition {
+                name: builtin.name.to_string(),
+                comment,
+                source: builtin.js.to_string(),
+            }]
+        }
+    });
+    support.chain(builtins).collect()
+}
+
+fn uncurried(builtin: &Builtin) -> String {
+    format!("{}${}", builtin.name, builtin.arity)
+}
+
+/// `(a) => (b) => name$2(a, b)`, where lazy arguments are wrapped in thunks.
+fn curried(builtin: &Builtin) -> String {
+    let params: Vec<String> = (0..builtin.arity)
+        .map(|i| char::from(b'a' + i as u8).to_string())
+        .collect();
+    let args: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            if builtin.lazy.contains(&i) {
+                format!("() => {}", param)
+            } else {
+                param.clone()
+            }
+        })
+        .collect();
+    let arrows: Vec<String> = params
+        .iter()
+        .map(|param| format!("({}) =>", param))
+        .collect();
+    format!(
+        "{} {}({})",
+        arrows.join(" "),
+        uncurried(builtin),
+        args.join(", ")
+    )
+}
+
+/// Source of the std module that generated code loads, which is shipped as
+/// js/std.js and js/esm/std.js.
+pub fn std(module: Module) -> String {
+    let mut std = String::from(
+        "// Generated from the builtins in src/stdlib.rs by `purec runtime`, do not\n\
+         // edit.\n",
+    );
+    let definitions = definitions();
+    let export = match module {
+        Module::Cjs => "",
+        Module::Esm => "export ",
+    };
+    for definition in definitions.iter() {
+        if !definition.comment.is_empty() {
+            std.push('\n');
+            for line in definition.comment.lines() {
+                std.push_str(&format!("// {}\n", line));
+            }
+        }
+        std.push_str(&format!(
+            "{}const {} = {};\n",
+            export, definition.name, definition.source
+        ));
+    }
+    if let Module::Cjs = module {
+        std.push_str("\nmodule.exports = {\n");
+        for definition in definitions.iter() {
+            std.push_str(&format!("  {},\n", definition.name));
+        }
+        std.push_str("};\n");
+    }
+    std
+}
+
+/// Source of a `std` object holding only the `used` runtime functions (named
+/// without the `std.` prefix) and whatever they depend on, in the order they
+/// are defined in std.js.
+pub fn bundle(used: &BTreeSet<String>) -> String {
+    let definitions = definitions();
+    let mut needed: BTreeSet<&str> = definitions
+        .iter()
+        .map(|definition| definition.name.as_str())
+        .filter(|name| used.contains(*name))
+        .collect();
+    loop {
+        let dependencies: BTreeSet<&str> = definitions
+            .iter()
+            .filter(|definition| needed.contains(definition.name.as_str()))
+            .flat_map(|definition| {
+                definitions
+                    .iter()
+                    .map(|dep| dep.name.as_str())
+                    .filter(|dep| mentions(&definition.source, dep))
+            })
+            .collect();
+        if dependencies.is_subset(&needed) {
+            break;
+        }
+        needed.extend(dependencies);
+    }
+    let included: Vec<&Definition> = definitions
+        .iter()
+        .filter(|definition| needed.contains(definition.name.as_str()))
+        .collect();
+    let consts: Vec<String> = included
+        .iter()
+        .map(|definition| {
+            format!(
+                "  const {} = {};",
+                definition.name,
+                indent(&definition.source)
+            )
+        })
+        .collect();
+    let names: Vec<&str> = included
+        .iter()
+        .map(|definition| definition.name.as_str())
+        .collect();
+    format!(
+        "const std = (() => {{\n{}\n  return {{ {} }};\n}})();",
+        consts.join("\n"),
+        names.join(", ")
+    )
+}
+
+/// Whether `name` occurs in `body` as a whole word.
+fn mentions(body: &str, name: &str) -> bool {
+    body.split(|c: char| !is_identifier_char(c))
+        .any(|word| word == name)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+fn indent(body: &str) -> String {
+    body.replace('\n', "\n  ")
+}
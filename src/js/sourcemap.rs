@@ -0,0 +1,99 @@
+use super::print::Mapping;
+
+const BASE64: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Source map (revision 3) from one generated file back to one Pure source.
+pub struct SourceMap {
+    /// Name of the generated file.
+    pub file: String,
+    /// Path to the source, relative to the source map.
+    pub source: String,
+    pub content: String,
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"version":3,"file":{},"sources":[{}],"sourcesContent":[{}],"names":[],"mappings":"{}"}}"#,
+            json_string(&self.file),
+            json_string(&self.source),
+            json_string(&self.content),
+            self.encode_mappings()
+        )
+    }
+
+    /// Mappings are grouped by generated line. Each segment holds the
+    /// generated column, the source index, and the source line and column,
+    /// all relative to the previous segment (the generated column only within
+    /// its line) and VLQ encoded.
+    fn encode_mappings(&self) -> String {
+        let mut mappings = self.mappings.clone();
+        mappings.sort_by_key(|m| (m.line, m.column));
+        mappings.dedup_by_key(|m| (m.line, m.column));
+
+        let mut encoded = String::new();
+        let mut line = 0;
+        let mut column = 0;
+        let mut source_line = 0;
+        let mut source_column = 0;
+        for (i, mapping) in mappings.iter().enumerate() {
+            if mapping.line > line {
+                encoded.push_str(&";".repeat(mapping.line - line));
+                line = mapping.line;
+                column = 0;
+            } else if i > 0 {
+                encoded.push(',');
+            }
+            let mapped_line = mapping.source.line as i64 - 1;
+            let mapped_column = mapping.source.column as i64 - 1;
+            vlq(&mut encoded, mapping.column as i64 - column);
+            vlq(&mut encoded, 0);
+            vlq(&mut encoded, mapped_line - source_line);
+            vlq(&mut encoded, mapped_column - source_column);
+            column = mapping.column as i64;
+            source_line = mapped_line;
+            source_column = mapped_column;
+        }
+        encoded
+    }
+}
+
+fn vlq(out: &mut String, value: i64) {
+    let mut value = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+        if value > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                json.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,455 @@
+use crate::ast::{Declaration, Expr, Span, AST};
+use crate::parser::{PureParser, Rule};
+use crate::stdlib::{self, BUILTINS};
+use crate::valid::Valid;
+use pest::error::InputLocation;
+use pest::Parser;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// Serves the Language Server Protocol over `input` and `output` until the
+/// client sends `exit`, and returns whether it asked to shut down first.
+///
+/// Documents are synced in full on every change and checked like `purec`
+/// checks a file: syntax errors, then validation. Positions are converted
+/// between byte offsets and the UTF-16 columns of the protocol.
+pub fn serve(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<bool> {
+    let mut server = Server {
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    while let Some(message) = receive(input)? {
+        let Some(method) = message["method"].as_str() else {
+            continue;
+        };
+        if method == "exit" {
+            break;
+        }
+        let params = &message["params"];
+        match message.get("id") {
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Some(result) => {
+                        json!({"jsonrpc": "2.0", "id": id, "result": result})
+                    }
+                    None => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32601,
+                            "message": format!("Unknown method: {}", method),
+                        },
+                    }),
+                };
+                send(output, &response)?;
+            }
+            None => {
+                if let Some(notification) = server.notify(method, params) {
+                    send(output, &notification)?;
+                }
+            }
+        }
+    }
+    Ok(server.shutdown)
+}
+
+/// Reads one message, framed by a `Content-Length` header.
+fn receive(input: &mut dyn BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Message without Content-Length",
+        ));
+    };
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn send(output: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    /// Result of a request, or `None` for methods that aren't supported.
+    fn request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self.documents.get(uri);
+        let position = &params["position"];
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {"triggerCharacters": ["."]},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": {
+                    "name": "purec",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/definition" => document
+                .and_then(|document| {
+                    let id = document.id_at(document.offset(position))?;
+                    let decl = document.declaration(id)?;
+                    Some(document.location(uri, document.name_span(decl)))
+                })
+                .unwrap_or(Value::Null),
+            "textDocument/references" => document
+                .and_then(|document| {
+                    let id = document.id_at(document.offset(position))?;
+                    let include = &params["context"]["includeDeclaration"];
+                    let declaration = document
+                        .declaration(id)
+                        .filter(|_| include.as_bool() == Some(true))
+                        .map(|decl| document.name_span(decl));
+                    let references = declaration
+                        .into_iter()
+                        .chain(document.references(id))
+                        .map(|span| document.location(uri, span))
+                        .collect();
+                    Some(Value::Array(references))
+                })
+                .unwrap_or(Value::Null),
+            "textDocument/hover" => document
+                .and_then(|document| {
+                    let id = document.id_at(document.offset(position))?;
+                    let source = match document.declaration(id) {
+                        Some(decl) => document.source(decl.span).to_string(),
+                        None => {
+                            format!("{} :: {}", id, stdlib::builtin(id)?.ty)
+                        }
+                    };
+                    Some(json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```pure\n{}\n```", source),
+                        },
+                    }))
+                })
+                .unwrap_or(Value::Null),
+            "textDocument/completion" => document
+                .map(|document| Value::Array(document.completions()))
+                .unwrap_or(Value::Null),
+            "textDocument/documentSymbol" => document
+                .map(|document| Value::Array(document.symbols()))
+                .unwrap_or(Value::Null),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Handles a notification, and returns the one to send in response, if
+    /// any.
+    fn notify(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?.to_string();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Changes are synced in full, so the last one is the document.
+            "textDocument/didChange" => {
+                params["contentChanges"].as_array()?.last()?["text"].as_str()
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return Some(publish(&uri, vec![]));
+            }
+            _ => None,
+        }?;
+        let document = Document::new(text.to_string());
+        let diagnostics = document.diagnostics();
+        self.documents.insert(uri.clone(), document);
+        Some(publish(&uri, diagnostics))
+    }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+struct Document {
+    text: String,
+    /// The declarations, even if they don't pass validation, unless there is
+    /// a syntax error.
+    ast: Option<AST>,
+    /// Errors, located by byte offsets.
+    errors: Vec<(usize, usize, String)>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let parsed = PureParser::parse(Rule::file, &text)
+            .map_err(|error| {
+                let (start, end) = match error.location {
+                    InputLocation::Pos(pos) => (pos, pos),
+                    InputLocation::Span(span) => span,
+                };
+                (start, end, error.variant.message().to_string())
+            })
+            .and_then(|pairs| {
+                AST::default()
+                    .declared(pairs)
+                    .map_err(|error| (0, 0, error))
+            });
+        match parsed {
+            Err(error) => Self {
+                text,
+                ast: None,
+                errors: vec![error],
+            },
+            Ok(ast) => {
+                let errors = match ast.validate() {
+                    Err(error) => Self::locate(&ast, error),
+                    Ok(()) => vec![],
+                };
+                Self {
+                    text,
+                    ast: Some(ast),
+                    errors,
+                }
+            }
+        }
+    }
+
+    /// Validation errors are about the program as a whole. Those about
+    /// references are reported at each of them, the rest at the start.
+    fn locate(ast: &AST, error: String) -> Vec<(usize, usize, String)> {
+        let index = stdlib::index();
+        let known = |id: &String| {
+            ast.declarations.contains_key(id) || index.contains_key(id)
+        };
+        let mut errors = vec![];
+        for decl in ast.declarations.values() {
+            walk(&decl.expr, &mut vec![], &mut |expr, scope| match expr {
+                Expr::ID(id, span) if !known(id) => errors.push((
+                    span.start.offset,
+                    span.end.offset,
+                    format!("Unknown reference: {}", id),
+                )),
+                Expr::Name(name, span) if !scope.contains(&name.as_str()) => {
+                    errors.push((
+                        span.start.offset,
+                        span.end.offset,
+                        format!("Unbound name: {}", name),
+                    ))
+                }
+                _ => {}
+            });
+        }
+        if errors.is_empty() {
+            errors.push((0, 0, error));
+        }
+        errors.sort();
+        errors
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        self.errors
+            .iter()
+            .map(|(start, end, message)| {
+                json!({
+                    "range": self.range(*start, *end),
+                    "severity": 1,
+                    "source": "purec",
+                    "message": message,
+                })
+            })
+            .collect()
+    }
+
+    fn declarations(&self) -> Vec<&Declaration> {
+        let mut declarations: Vec<&Declaration> = self
+            .ast
+            .iter()
+            .flat_map(|ast| ast.declarations.values())
+            .collect();
+        declarations.sort_by_key(|decl| decl.span.start.offset);
+        declarations
+    }
+
+    fn declaration(&self, id: &str) -> Option<&Declaration> {
+        self.ast.as_ref()?.declarations.get(id)
+    }
+
+    /// The declared or referenced id at the offset.
+    fn id_at(&self, offset: usize) -> Option<&str> {
+        let contains =
+            |(start, end): (usize, usize)| start <= offset && offset <= end;
+        let mut found = None;
+        for decl in self.declarations() {
+            if contains(self.name_span(decl)) {
+                return Some(&decl.id);
+            }
+            walk(&decl.expr, &mut vec![], &mut |expr, _| {
+                if let Expr::ID(id, span) = expr {
+                    if contains((span.start.offset, span.end.offset)) {
+                        found = Some(id.as_str());
+                    }
+                }
+            });
+        }
+        found
+    }
+
+    fn references(&self, id: &str) -> Vec<(usize, usize)> {
+        let mut references = vec![];
+        for decl in self.declarations() {
+            walk(&decl.expr, &mut vec![], &mut |expr, _| {
+                if let Expr::ID(reference, span) = expr {
+                    if reference == id {
+                        references.push((span.start.offset, span.end.offset));
+                    }
+                }
+            });
+        }
+        references
+    }
+
+    fn completions(&self) -> Vec<Value> {
+        let declared = self.declarations().into_iter().map(|decl| {
+            let kind = match decl.expr {
+                Expr::Func(..) => 3,
+                _ => 21,
+            };
+            json!({"label": decl.id, "kind": kind})
+        });
+        let builtins = BUILTINS.iter().map(|builtin| {
+            json!({"label": builtin.id(), "kind": 3, "detail": builtin.ty})
+        });
+        declared.chain(builtins).collect()
+    }
+
+    fn symbols(&self) -> Vec<Value> {
+        self.declarations()
+            .into_iter()
+            .map(|decl| {
+                let (start, end) = self.name_span(decl);
+                let kind = match decl.expr {
+                    Expr::Func(..) => 12,
+                    _ => 14,
+                };
+                json!({
+                    "name": decl.id,
+                    "kind": kind,
+                    "range": self.range(
+                        decl.span.start.offset,
+                        decl.span.end.offset,
+                    ),
+                    "selectionRange": self.range(start, end),
+                })
+            })
+            .collect()
+    }
+
+    /// The declared id, or operator in parentheses, before the `:=`.
+    fn name_span(&self, decl: &Declaration) -> (usize, usize) {
+        let start = decl.span.start.offset;
+        let source = self.source(decl.span);
+        let name = source[..source.find(":=").unwrap_or(0)].trim_end();
+        (start, start + name.len())
+    }
+
+    fn source(&self, span: Span) -> &str {
+        &self.text[span.start.offset..span.end.offset]
+    }
+
+    fn location(&self, uri: &str, (start, end): (usize, usize)) -> Value {
+        json!({"uri": uri, "range": self.range(start, end)})
+    }
+
+    fn range(&self, start: usize, end: usize) -> Value {
+        json!({"start": self.position(start), "end": self.position(end)})
+    }
+
+    fn position(&self, offset: usize) -> Value {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        json!({
+            "line": before.matches('\n').count(),
+            "character": before[line_start..].encode_utf16().count(),
+        })
+    }
+
+    fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let line_start: usize = self
+            .text
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum();
+        let mut units = 0;
+        for (i, c) in self.text[line_start..].char_indices() {
+            if units >= character || c == '\n' {
+                return line_start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+}
+
+/// Visits every subexpression, along with the lambda parameters in scope.
+fn walk<'a>(
+    expr: &'a Expr,
+    scope: &mut Vec<&'a str>,
+    visit: &mut dyn FnMut(&'a Expr, &[&'a str]),
+) {
+    visit(expr, scope);
+    match expr {
+        Expr::Call(f, args, _) => {
+            walk(f, scope, visit);
+            args.iter().for_each(|arg| walk(arg, scope, visit));
+        }
+        Expr::Func(params, body, _) => {
+            let depth = scope.len();
+            scope.extend(params.iter().map(String::as_str));
+            walk(body, scope, visit);
+            scope.truncate(depth);
+        }
+        _ => {}
+    }
+}
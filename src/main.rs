@@ -5,97 +5,647 @@ extern crate pest;
 mod asm;
 mod ast;
 mod def;
+mod eval;
+mod fixity;
+mod fmt;
 mod js;
+mod lsp;
+mod migrate;
 mod parser;
+mod repl;
 mod stdlib;
 mod valid;
 
 use ast::AST;
-use clap::Parser as Clap;
+use clap::{Args, Parser as Clap, Subcommand, ValueEnum};
 use parser::{PureParser, Rule};
-use pest::iterators::Pairs;
 use pest::Parser;
-use std::io::{self, Write};
-use std::{fs, process};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::{env, fs, panic, process, thread};
+
+/// Exit status when purec can't read or write a file. Errors in the program
+/// exit with 1, and bad usage with 2.
+const EXIT_IO: i32 = 3;
+
+/// Stack of the thread that purec runs on, large enough for `eval` to reach
+/// [`eval::MAX_DEPTH`] even in a debug build.
+const STACK_SIZE: usize = 1 << 30;
 
 #[derive(Clap, Debug)]
 #[command(version, about, long_about = None)]
 struct App {
-    /// Path to source code file
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a program
+    Build(Build),
+    /// Check a program for errors without compiling it
+    Check(Check),
+    /// Compile a program and run it with node
+    Run(Run),
+    /// Print an intermediate representation of a program
+    Emit(Emit),
+    /// Run a program by interpreting it, without compiling it
+    Eval(Eval),
+    /// Evaluate expressions and add declarations interactively
+    Repl,
+    /// Serve the Language Server Protocol over stdio
+    Lsp,
+    /// Rewrite files in the canonical style
+    Fmt(Fmt),
+    /// Rewrite a file with bare declarations to use namespaced ids
+    Migrate(Migrate),
+    /// Print the std runtime that generated code relies on
+    Runtime(Runtime),
+}
+
+/// What code is generated for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Target {
+    /// JavaScript, run by node
+    #[default]
+    #[value(alias = "js")]
+    Node,
+    /// Bytecode image for the Pure VM
+    Vm,
+}
+
+impl Target {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Node => "js",
+            Self::Vm => "bin",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct Linkage {
+    /// Compile a library that exports declarations instead of running
+    /// main.main
+    #[arg(long)]
+    lib: bool,
+
+    /// Declarations exported by a library [default: all public ones]
+    #[arg(long, value_delimiter = ',', requires = "lib")]
+    export: Vec<String>,
+}
+
+impl Linkage {
+    /// Programs must have an entrypoint, libraries must export something that
+    /// exists.
+    fn check(&self, ast: &AST) {
+        let checked = if self.lib {
+            ast.check_declared(&self.export)
+        } else {
+            ast.check_entrypoint_present()
+        };
+        if let Err(semantic_error) = checked {
+            exit(format!("Semantic error:\n{}", semantic_error));
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct JsArgs {
+    /// Module system of the generated JS
+    #[arg(long, value_enum, default_value_t = js::Module::Cjs)]
+    module: js::Module,
+
+    /// Inline the std runtime into the generated JS
+    #[arg(long)]
+    bundle: bool,
+
+    /// Minify the generated JS
+    #[arg(long)]
+    minify: bool,
+
+    /// Run mutually recursive tail calls on a trampoline
+    #[arg(long)]
+    trampoline: bool,
+}
+
+impl JsArgs {
+    fn options(&self, linkage: &Linkage) -> js::Options {
+        js::Options {
+            module: self.module,
+            lib: linkage.lib,
+            exports: linkage.export.clone(),
+            bundle: self.bundle,
+            minify: self.minify,
+            trampoline: self.trampoline,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct Input {
+    /// Path to source code file, or `-` for stdin
     source: String,
 
-    /// Path to output file
-    #[arg(short, long, default_value_t = String::from("main.js"))]
-    output: String,
+    /// Read the program as AST JSON, as `purec emit ast-json` prints it
+    /// [default: for files ending in .json]
+    #[arg(long)]
+    ast_json: bool,
+}
 
-    /// Output architecture (vm | js)
-    #[arg(long, default_value_t = String::from("node"))]
-    arch: String,
+/// A program as it was read.
+struct Source {
+    ast: AST,
+    comments: Vec<ast::Comment>,
+    /// The Pure source, unless the program was read as AST JSON.
+    text: Option<String>,
 }
 
-impl App {
+impl Input {
+    fn load(&self) -> Result<Source, io::Error> {
+        let text = read_source(&self.source)?;
+        if !self.ast_json && !self.source.ends_with(".json") {
+            return Ok(Source {
+                ast: parse(&text),
+                comments: ast::comments(&text),
+                text: Some(text),
+            });
+        }
+        let json = match serde_json::from_str(&text) {
+            Err(error) => exit(format!("Syntax error:\n{}", error)),
+            Ok(json) => json,
+        };
+        match ast::json::from_json(&json) {
+            Err(error) => exit(format!("Invalid AST JSON:\n{}", error)),
+            Ok((ast, comments)) => Ok(Source {
+                ast,
+                comments,
+                text: None,
+            }),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct Build {
+    #[command(flatten)]
+    input: Input,
+
+    /// Path to output file, or `-` for stdout [default: the source file with
+    /// the target's extension, or stdout for stdin]
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// What to generate code for
+    #[arg(long, value_enum, default_value_t = Target::Node)]
+    target: Target,
+
+    #[command(flatten)]
+    linkage: Linkage,
+
+    #[command(flatten)]
+    js: JsArgs,
+
+    /// Write a source map for the generated JS next to it
+    #[arg(long)]
+    source_map: bool,
+
+    /// Also write these representations of the program next to the output,
+    /// as `<OUTPUT>.<ext>`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    emit: Vec<EmitKind>,
+}
+
+impl Build {
     fn run(self) -> Result<(), io::Error> {
-        self.compile(self.read_source()?)
+        let Source {
+            ast,
+            comments,
+            text,
+        } = self.input.load()?;
+        self.linkage.check(&ast);
+        let output = self.output_path();
+        if !self.emit.is_empty() && output == "-" {
+            exit("--emit needs an output file".to_string());
+        }
+        for kind in self.emit.iter() {
+            let dump = kind.dump(&ast, &comments, &self.linkage, &self.js);
+            let path = format!("{}.{}", output, kind.extension());
+            write_output(&path, dump.as_bytes())?;
+        }
+        let code = match self.target {
+            Target::Vm => vm_image(ast, &self.linkage),
+            Target::Node => {
+                let program =
+                    js::Program::new(ast, &self.js.options(&self.linkage));
+                if self.source_map {
+                    self.write_source_map(program, text, &output)?
+                } else {
+                    program.into()
+                }
+                .into_bytes()
+            }
+        };
+        write_output(&output, &code)
     }
 
-    fn read_source(&self) -> io::Result<String> {
-        fs::read_to_string(&self.source)
+    fn output_path(&self) -> String {
+        if let Some(output) = &self.output {
+            return output.clone();
+        }
+        let source = &self.input.source;
+        if source == "-" {
+            return "-".to_string();
+        }
+        let output = Path::new(source)
+            .with_extension(self.target.extension())
+            .to_string_lossy()
+            .to_string();
+        if output == *source {
+            exit(format!("Output would overwrite {}, pass -o", source));
+        }
+        output
     }
 
-    fn compile(&self, input: String) -> Result<(), io::Error> {
-        self.generate_executable_code(Self::parse_input(input))
+    /// Writes `<output>.map` and returns the code that refers to it.
+    fn write_source_map(
+        &self,
+        program: js::Program,
+        text: Option<String>,
+        output: &str,
+    ) -> Result<String, io::Error> {
+        let Some(text) = text else {
+            exit("Source maps need Pure source, not AST JSON".to_string());
+        };
+        if self.input.source == "-" || output == "-" {
+            exit("Source maps need source and output files".to_string());
+        }
+        let (code, mappings) = program.render();
+        let output = Path::new(output);
+        let file = output.file_name().unwrap().to_string_lossy().to_string();
+        let map_file = format!("{}.map", file);
+        let source_map = js::SourceMap {
+            file,
+            source: relative_path(
+                Path::new(&self.input.source),
+                output.parent().unwrap(),
+            )?,
+            content: text,
+            mappings,
+        };
+        let map_path = output.with_file_name(&map_file);
+        write_output(
+            &map_path.to_string_lossy(),
+            source_map.to_json().as_bytes(),
+        )?;
+        Ok(format!("{}//# sourceMappingURL={}\n", code, map_file))
     }
+}
 
-    fn parse_input(input: String) -> AST {
-        let parsed = PureParser::parse(Rule::file, &input);
-        match parsed {
-            Err(syntax_error) => {
-                exit(format!("Syntax error:\n{}", syntax_error));
-                AST::default()
-            }
-            Ok(pairs) => Self::form_ast(pairs),
+#[derive(Args, Debug)]
+struct Check {
+    #[command(flatten)]
+    input: Input,
+
+    #[command(flatten)]
+    linkage: Linkage,
+}
+
+impl Check {
+    fn run(self) -> Result<(), io::Error> {
+        let ast = self.input.load()?.ast;
+        self.linkage.check(&ast);
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+struct Run {
+    #[command(flatten)]
+    input: Input,
+
+    /// Run mutually recursive tail calls on a trampoline
+    #[arg(long)]
+    trampoline: bool,
+}
+
+impl Run {
+    /// Runs a bundled build, so that it needs no std.js, and exits with the
+    /// program's status.
+    fn run(self) -> Result<(), io::Error> {
+        let ast = self.input.load()?.ast;
+        if let Err(semantic_error) = ast.check_entrypoint_present() {
+            exit(format!("Semantic error:\n{}", semantic_error));
+        }
+        let options = js::Options {
+            bundle: true,
+            trampoline: self.trampoline,
+            ..js::Options::default()
+        };
+        let code: String = js::Program::new(ast, &options).into();
+        let script =
+            env::temp_dir().join(format!("purec-run-{}.js", process::id()));
+        write_output(&script.to_string_lossy(), code.as_bytes())?;
+        let status = process::Command::new("node").arg(&script).status();
+        let _ = fs::remove_file(&script);
+        let status = status.map_err(|error| in_file("node", error))?;
+        process::exit(status.code().unwrap_or(1))
+    }
+}
+
+/// Intermediate representations of a program, which `purec emit` prints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum EmitKind {
+    /// The parsed AST
+    Ast,
+    /// The parsed AST as JSON
+    AstJson,
+    /// The VM definitions, with symbolic references and their offsets
+    Def,
+    /// The VM program, as it is laid out in the image
+    Asm,
+    /// The JS tree, before it is printed
+    Js,
+}
+
+impl EmitKind {
+    /// Extension of the file that `purec build --emit` writes it to.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Ast => "ast",
+            Self::AstJson => "ast.json",
+            Self::Def => "def",
+            Self::Asm => "asm",
+            Self::Js => "jstree",
         }
     }
 
-    fn form_ast(pairs: Pairs<'_, Rule>) -> AST {
-        let attempt: Result<AST, String> = pairs.try_into();
-        match attempt {
-            Err(semantic_error) => {
-                exit(format!("Semantic error:\n{}", semantic_error));
-                AST::default()
+    fn dump(
+        self,
+        ast: &AST,
+        comments: &[ast::Comment],
+        linkage: &Linkage,
+        js: &JsArgs,
+    ) -> String {
+        match self {
+            Self::Ast => ast.to_string(),
+            Self::AstJson => {
+                format!("{:#}\n", ast::json::to_json(ast, comments))
+            }
+            Self::Def => vm_program(ast.clone(), linkage).to_string(),
+            Self::Asm => {
+                let program: asm::Program =
+                    vm_program(ast.clone(), linkage).into();
+                program.to_string()
+            }
+            Self::Js => {
+                js::Program::new(ast.clone(), &js.options(linkage)).to_string()
             }
-            Ok(ast) => ast,
         }
     }
+}
+
+#[derive(Args, Debug)]
+struct Emit {
+    /// What to print
+    #[arg(value_enum)]
+    kind: EmitKind,
+
+    #[command(flatten)]
+    input: Input,
+
+    /// Path to output file, or `-` for stdout
+    #[arg(short, long, default_value_t = String::from("-"))]
+    output: String,
+
+    #[command(flatten)]
+    linkage: Linkage,
+
+    #[command(flatten)]
+    js: JsArgs,
+}
+
+impl Emit {
+    fn run(self) -> Result<(), io::Error> {
+        let Source { ast, comments, .. } = self.input.load()?;
+        self.linkage.check(&ast);
+        let dump = self.kind.dump(&ast, &comments, &self.linkage, &self.js);
+        write_output(&self.output, dump.as_bytes())
+    }
+}
+
+#[derive(Args, Debug)]
+struct Migrate {
+    /// Path to source code file, rewritten in place, or `-` to migrate stdin
+    /// to stdout
+    source: String,
+
+    /// Namespace for the bare declarations
+    #[arg(short, long, default_value_t = String::from("main"))]
+    namespace: String,
+}
 
-    fn generate_executable_code(&self, ast: AST) -> Result<(), io::Error> {
-        match self.arch.as_str() {
-            "vm" => {
-                let program: asm::Program = def::Program::from(ast).into();
-                fs::File::create(&self.output)
-                    .expect("Failed to create executable file")
-                    .write_all(program.as_vec().as_slice())
+impl Migrate {
+    fn run(self) -> Result<(), io::Error> {
+        let input = read_source(&self.source)?;
+        match migrate::migrate(&input, &self.namespace) {
+            Err(error) => exit(error),
+            Ok(output) => write_output(&self.source, output.as_bytes()),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct Runtime {
+    /// `node` prints std.js, `vm` the builtin dispatch table
+    #[arg(long, value_enum, default_value_t = Target::Node)]
+    target: Target,
+
+    /// Module system of the JS runtime
+    #[arg(long, value_enum, default_value_t = js::Module::Cjs)]
+    module: js::Module,
+}
+
+impl Runtime {
+    fn run(self) -> Result<(), io::Error> {
+        let runtime = match self.target {
+            Target::Node => js::std_module(self.module),
+            Target::Vm => asm::dispatch_table(),
+        };
+        write_output("-", runtime.as_bytes())
+    }
+}
+
+#[derive(Args, Debug)]
+struct Fmt {
+    /// Paths to source code files, rewritten in place, or `-` to format stdin
+    /// to stdout
+    #[arg(required = true)]
+    sources: Vec<String>,
+
+    /// Only check that the files are formatted, failing if any is not
+    #[arg(long)]
+    check: bool,
+}
+
+impl Fmt {
+    fn run(self) -> Result<(), io::Error> {
+        let mut unformatted = vec![];
+        for source in self.sources.iter() {
+            let input = read_source(source)?;
+            let output = match fmt::format(&input) {
+                Err(error) => exit(format!("{}: {}", source, error)),
+                Ok(output) => output,
+            };
+            if output != input && self.check {
+                unformatted.push(source.as_str());
+            } else if output != input || source == "-" {
+                write_output(source, output.as_bytes())?;
             }
-            "node" => {
-                let program: js::Program = ast.into();
-                let code: String = program.into();
-                fs::File::create(&self.output)
-                    .expect("Failed to create executable file")
-                    .write_all(code.as_bytes())
+        }
+        if !unformatted.is_empty() {
+            exit(format!("Not formatted: {}", unformatted.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+struct Eval {
+    #[command(flatten)]
+    input: Input,
+}
+
+impl Eval {
+    fn run(self) -> Result<(), io::Error> {
+        let ast = self.input.load()?.ast;
+        if let Err(semantic_error) = ast.check_entrypoint_present() {
+            exit(format!("Semantic error:\n{}", semantic_error));
+        }
+        match eval::run(&ast, &mut io::stdout()) {
+            Err(panic @ eval::Error::Panic { .. }) => {
+                eprintln!("{}", panic);
+                process::exit(101);
             }
-            unknown_arch => {
-                exit(format!("Unknown arch '{}'", unknown_arch));
-                Ok(())
+            Err(error) => exit(format!("Runtime error:\n{}", error)),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+impl App {
+    fn run(self) -> Result<(), io::Error> {
+        match self.command {
+            Command::Build(build) => build.run(),
+            Command::Check(check) => check.run(),
+            Command::Run(run) => run.run(),
+            Command::Emit(emit) => emit.run(),
+            Command::Eval(eval) => eval.run(),
+            Command::Repl => repl::repl(
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+                io::stdin().is_terminal(),
+            ),
+            Command::Lsp => {
+                let shutdown =
+                    lsp::serve(&mut io::stdin().lock(), &mut io::stdout())?;
+                // The protocol wants an exit without shutdown to fail.
+                process::exit(if shutdown { 0 } else { 1 })
             }
+            Command::Fmt(fmt) => fmt.run(),
+            Command::Migrate(migrate) => migrate.run(),
+            Command::Runtime(runtime) => runtime.run(),
+        }
+    }
+}
+
+fn parse(input: &str) -> AST {
+    let pairs = match PureParser::parse(Rule::file, input) {
+        Err(syntax_error) => exit(format!("Syntax error:\n{}", syntax_error)),
+        Ok(pairs) => pairs,
+    };
+    match pairs.try_into() {
+        Err(semantic_error) => {
+            exit(format!("Semantic error:\n{}", semantic_error))
         }
+        Ok(ast) => ast,
+    }
+}
+
+fn vm_program(ast: AST, linkage: &Linkage) -> def::Program {
+    if linkage.lib {
+        exit("Libraries can only be compiled for node".to_string());
+    }
+    def::Program::try_from(ast).unwrap_or_else(|error| exit(error))
+}
+
+fn vm_image(ast: AST, linkage: &Linkage) -> Vec<u8> {
+    let program: asm::Program = vm_program(ast, linkage).into();
+    program.as_vec()
+}
+
+/// Reads a file, or stdin for `-`.
+fn read_source(source: &str) -> Result<String, io::Error> {
+    if source == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        return Ok(input);
     }
+    fs::read_to_string(source).map_err(|error| in_file(source, error))
 }
 
-fn main() -> Result<(), io::Error> {
-    return App::parse().run();
+/// Writes a file, or stdout for `-`.
+fn write_output(output: &str, content: &[u8]) -> Result<(), io::Error> {
+    if output == "-" {
+        return io::stdout().write_all(content);
+    }
+    fs::write(output, content).map_err(|error| in_file(output, error))
+}
+
+fn in_file(path: &str, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path, error))
+}
+
+/// Path to `path` from the `dir` directory, which both have to exist.
+fn relative_path(path: &Path, dir: &Path) -> Result<String, io::Error> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let path = fs::canonicalize(path)?;
+    let dir = fs::canonicalize(dir)?;
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let relative: PathBuf = dir
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect();
+    Ok(relative.to_string_lossy().to_string())
+}
+
+fn main() {
+    let app = App::parse();
+    let result = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| app.run())
+        .and_then(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|panic| panic::resume_unwind(panic))
+        });
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(EXIT_IO);
+    }
 }
 
-fn exit(message: String) {
+fn exit(message: String) -> ! {
     eprintln!("{}", message);
     process::exit(1);
 }
@@ -0,0 +1,118 @@
+use pest::iterators::Pair;
+use pest::{Parser, Span};
+use std::collections::HashSet;
+
+#[derive(Parser)]
+#[grammar = "grammar.pest"]
+#[grammar = "legacy_grammar.pest"]
+struct LegacyParser;
+
+/// Rewrites source written against the pre-namespace grammar: every bare
+/// declaration `x := ...` becomes `namespace.x := ...` and every reference to
+/// it is qualified the same way. Comments and layout are left untouched.
+pub fn migrate(input: &str, namespace: &str) -> Result<String, String> {
+    check_namespace(namespace)?;
+    let declarations: Vec<Pair<Rule>> =
+        LegacyParser::parse(Rule::legacy_file, input)
+            .map_err(|syntax_error| format!("Syntax error:\n{}", syntax_error))?
+            .filter(|pair| matches!(pair.as_rule(), Rule::legacy_declaration))
+            .collect();
+
+    let bare: HashSet<&str> = declarations
+        .iter()
+        .map(|decl| decl.clone().into_inner().next().unwrap())
+        .filter(|id| matches!(id.as_rule(), Rule::name))
+        .map(|id| id.as_str())
+        .collect();
+
+    let mut edits = vec![];
+    for decl in declarations {
+        let mut it = decl.into_inner();
+        let id = it.next().unwrap();
+        if let Rule::name = id.as_rule() {
+            edits.push(id.as_span());
+        }
+        collect_references(it.next().unwrap(), &bare, &mut vec![], &mut edits);
+    }
+
+    let mut output = String::new();
+    let mut last = 0;
+    for span in edits {
+        output.push_str(&input[last..span.start()]);
+        output.push_str(&format!("{}.{}", namespace, span.as_str()));
+        last = span.end();
+    }
+    output.push_str(&input[last..]);
+    Ok(output)
+}
+
+/// Collects spans of the bare names that refer to top-level declarations, in
+/// source order. Names bound by an enclosing lambda are left alone.
+fn collect_references<'i>(
+    pair: Pair<'i, Rule>,
+    bare: &HashSet<&str>,
+    bound: &mut Vec<String>,
+    edits: &mut Vec<Span<'i>>,
+) {
+    match pair.as_rule() {
+        Rule::name => {
+            let name = pair.as_str();
+            if bare.contains(name) && !bound.iter().any(|b| b == name) {
+                edits.push(pair.as_span());
+            }
+        }
+        Rule::func => {
+            let (params, body): (Vec<_>, Vec<_>) = pair
+                .into_inner()
+                .partition(|pair| matches!(pair.as_rule(), Rule::param));
+            let scope = bound.len();
+            bound.extend(
+                params
+                    .into_iter()
+                    .map(|param| param.into_inner().as_str().to_string()),
+            );
+            for pair in body {
+                collect_references(pair, bare, bound, edits);
+            }
+            bound.truncate(scope);
+        }
+        // A name bound in a do block is in scope in the statements after it.
+        Rule::do_block => {
+            let scope = bound.len();
+            for statement in pair.into_inner() {
+                if let Rule::bind = statement.as_rule() {
+                    let mut pairs = statement.into_inner();
+                    let name = pairs.next().unwrap().as_str().to_string();
+                    collect_references(
+                        pairs.next().unwrap(),
+                        bare,
+                        bound,
+                        edits,
+                    );
+                    bound.push(name);
+                } else {
+                    collect_references(statement, bare, bound, edits);
+                }
+            }
+            bound.truncate(scope);
+        }
+        _ => {
+            for pair in pair.into_inner() {
+                collect_references(pair, bare, bound, edits);
+            }
+        }
+    }
+}
+
+fn check_namespace(namespace: &str) -> Result<(), String> {
+    let valid = [Rule::id, Rule::name].into_iter().any(|rule| {
+        LegacyParser::parse(rule, namespace)
+            .map(|pairs| pairs.as_str() == namespace)
+            .unwrap_or(false)
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid namespace '{}'", namespace))
+    }
+}
@@ -5,17 +5,13 @@ use pest::iterators::Pair;
 pub struct PureParser;
 
 pub fn is_not_eoi(pair: &Pair<Rule>) -> bool {
-    if let Rule::EOI = pair.as_rule() {
-        false
-    } else {
-        true
-    }
+    !matches!(pair.as_rule(), Rule::EOI)
+}
+
+pub fn is_fixity(pair: &Pair<Rule>) -> bool {
+    matches!(pair.as_rule(), Rule::fixity)
 }
 
 pub fn is_param(pair: &Pair<Rule>) -> bool {
-    if let Rule::param = pair.as_rule() {
-        true
-    } else {
-        false
-    }
+    matches!(pair.as_rule(), Rule::param)
 }
@@ -0,0 +1,82 @@
+use crate::ast::{Expr, AST};
+use crate::eval;
+use crate::parser::{PureParser, Rule};
+use pest::Parser;
+use std::io::{self, BufRead, Write};
+
+/// Reads a line at a time: fixities and declarations are added to the
+/// program, which is validated again, and expressions are evaluated in its
+/// context, with commands run. Results are printed with their type when it is
+/// known. Errors are reported and leave the program as it was.
+pub fn repl(
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    prompt: bool,
+) -> io::Result<()> {
+    let mut ast = AST::default();
+    let mut line = String::new();
+    loop {
+        if prompt {
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim();
+        match line {
+            "" => continue,
+            ":quit" | ":q" => break,
+            _ => {}
+        }
+        match read(&ast, line) {
+            Err(error) => writeln!(out, "{}", error)?,
+            Ok(Input::Declarations(extended)) => ast = extended,
+            Ok(Input::Expr(expr)) => {
+                let mut printed = vec![];
+                let result =
+                    eval::eval(&ast, &expr, &mut printed).map(|value| {
+                        match value.ty() {
+                            Some(ty) => format!("{} :: {}", value, ty),
+                            None => value.to_string(),
+                        }
+                    });
+                out.write_all(&printed)?;
+                match result {
+                    Ok(result) => writeln!(out, "{}", result)?,
+                    Err(error) => writeln!(out, "{}", error)?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+enum Input {
+    Declarations(AST),
+    Expr(Expr),
+}
+
+fn read(ast: &AST, line: &str) -> Result<Input, String> {
+    let expression = PureParser::parse(Rule::expression, line);
+    if let Ok(mut pairs) = expression {
+        let expr = Expr::parse(pairs.next().unwrap(), &ast.fixities)?;
+        ast.check_expr(&expr)
+            .map_err(|error| format!("Semantic error:\n{}", error))?;
+        return Ok(Input::Expr(expr));
+    }
+    let declarations = PureParser::parse(Rule::file, line);
+    match (declarations, expression) {
+        (Ok(pairs), _) => ast
+            .extended(pairs)
+            .map(Input::Declarations)
+            .map_err(|error| format!("Semantic error:\n{}", error)),
+        // Report the error of what the line looks like it was meant to be.
+        (Err(error), _) if line.contains(":=") || line.starts_with("infix") => {
+            Err(format!("Syntax error:\n{}", error))
+        }
+        (_, Err(error)) => Err(format!("Syntax error:\n{}", error)),
+        (_, Ok(_)) => unreachable!(),
+    }
+}
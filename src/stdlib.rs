@@ -2,26 +2,208 @@
 
 use std::collections::HashMap;
 
+/// Builtins, numbered as the VM knows them.
+///
+/// The numbers are part of the VM image format and frozen: a builtin keeps its
+/// id for good, and a new one takes the next free id, wherever it is declared.
+/// Ids of removed builtins are not reused.
+///
+/// The I32 arithmetic wraps around on overflow and division truncates toward
+/// zero, like Rust's `wrapping_*` methods on `i32`. The JS runtime implements
+/// exactly these semantics.
+///
+/// Partial builtins panic when applied outside their domain, `std.div` by
+/// zero with "division by zero". Under node, a panic stops the program, which
+/// reports `panic in <builtin>: <message>` on stderr and exits with status
+/// 101.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StdLib {
-    ID,
-    Iff,
+    ID = 0,
+    Iff = 1,
 
-    Add_I32,
-    Sub_I32,
-    Mul_I32,
-    Div_I32,
+    Add_I32 = 2,
+    Sub_I32 = 3,
+    Mul_I32 = 4,
+    Div_I32 = 5,
 
-    Prints,
+    Prints = 6,
+
+    Cmd = 7,
+    MapCmd = 8,
+    SwapCmd = 9,
+    ThenCmd = 10,
+    ChainCmd = 11,
+}
+
+impl StdLib {
+    /// Id that `PUSH_FN` refers to the builtin with.
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+/// A builtin, as every backend sees it. [`BUILTINS`] is the one place they
+/// are declared: the index of known ids, the VM dispatch table and the JS
+/// runtime are all derived from it.
+pub struct Builtin {
+    /// Name within the `std` namespace.
+    pub name: &'static str,
+    pub vm: StdLib,
+    pub arity: usize,
+    /// Type signature, for documentation.
+    pub ty: &'static str,
+    /// JS implementation that takes all arguments at once. Functions of
+    /// several parameters also get a curried version that defers to it.
+    pub js: &'static str,
+    /// Parameters, by index, that the implementation takes unevaluated, as
+    /// functions of no arguments. Saturated calls pass those arguments as
+    /// thunks, so that only the ones the builtin needs are evaluated.
+    pub lazy: &'static [usize],
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "id",
+        vm: StdLib::ID,
+        arity: 1,
+        ty: "Va. a -> a",
+        js: "(x) => x",
+        lazy: &[],
+    },
+    Builtin {
+        name: "iff",
+        vm: StdLib::Iff,
+        arity: 3,
+        ty: "Va. Bool -> a -> a -> a",
+        js: "(cond, x, y) => (cond ? x() : y())",
+        lazy: &[1, 2],
+    },
+    Builtin {
+        name: "add",
+        vm: StdLib::Add_I32,
+        arity: 2,
+        ty: "I32 -> I32 -> I32",
+        js: "(x, y) => (x + y) | 0",
+        lazy: &[],
+    },
+    Builtin {
+        name: "sub",
+        vm: StdLib::Sub_I32,
+        arity: 2,
+        ty: "I32 -> I32 -> I32",
+        js: "(x, y) => (x - y) | 0",
+        lazy: &[],
+    },
+    Builtin {
+        name: "mul",
+        vm: StdLib::Mul_I32,
+        arity: 2,
+        ty: "I32 -> I32 -> I32",
+        js: "(x, y) => Math.imul(x, y)",
+        lazy: &[],
+    },
+    Builtin {
+        name: "div",
+        vm: StdLib::Div_I32,
+        arity: 2,
+        ty: "I32 -> I32 -> I32",
+        js: r#"(x, y) =>
+  y === 0 ? panic("std.div", "division by zero") : (x / y) | 0"#,
+        lazy: &[],
+    },
+    Builtin {
+        name: "prints",
+        vm: StdLib::Prints,
+        arity: 1,
+        ty: "Str -> Cmd Str",
+        js: "(str) => () => {
+  console.log(str);
+  return str;
+}",
+        lazy: &[],
+    },
+    Builtin {
+        name: "cmd",
+        vm: StdLib::Cmd,
+        arity: 1,
+        ty: "Va. a -> Cmd a",
+        js: "(x) => () => x",
+        lazy: &[],
+    },
+    Builtin {
+        name: "mapCmd",
+        vm: StdLib::MapCmd,
+        arity: 2,
+        ty: "Va, b. (a -> b) -> Cmd a -> Cmd b",
+        js: "(f, c) => () => f(c())",
+        lazy: &[],
+    },
+    Builtin {
+        name: "swapCmd",
+        vm: StdLib::SwapCmd,
+        arity: 2,
+        ty: "Va, b. a -> Cmd b -> Cmd a",
+        js: "(def, c) => () => {
+  c();
+  return def;
+}",
+        lazy: &[],
+    },
+    Builtin {
+        name: "thenCmd",
+        vm: StdLib::ThenCmd,
+        arity: 2,
+        ty: "Va, b. Cmd a -> Cmd b -> Cmd b",
+        js: "(ca, cb) => () => {
+  ca();
+  return cb();
+}",
+        lazy: &[],
+    },
+    Builtin {
+        name: "chainCmd",
+        vm: StdLib::ChainCmd,
+        arity: 2,
+        ty: "Va, b. Cmd a -> (a -> Cmd b) -> Cmd b",
+        js: "(ca, f) => () => f(ca())()",
+        lazy: &[],
+    },
+];
+
+impl Builtin {
+    pub fn id(&self) -> String {
+        format!("std.{}", self.name)
+    }
+}
+
+/// The builtin with the qualified id, e.g. `std.add`.
+pub fn builtin(id: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.id() == id)
 }
 
 pub fn index() -> HashMap<String, usize> {
-    HashMap::from([
-        ("std.id".to_string(), StdLib::ID as usize),
-        ("std.iff".to_string(), StdLib::Iff as usize),
-        ("std.add".to_string(), StdLib::Add_I32 as usize),
-        ("std.sub".to_string(), StdLib::Sub_I32 as usize),
-        ("std.mul".to_string(), StdLib::Mul_I32 as usize),
-        ("std.div".to_string(), StdLib::Div_I32 as usize),
-        ("std.prints".to_string(), StdLib::Prints as usize),
-    ])
+    BUILTINS
+        .iter()
+        .map(|builtin| (builtin.id(), builtin.vm.id() as usize))
+        .collect()
+}
+
+/// Builtins that the runtime also provides uncurried, as `name$arity`, along
+/// with their arity.
+pub fn arities() -> HashMap<String, usize> {
+    BUILTINS
+        .iter()
+        .filter(|builtin| builtin.arity > 1)
+        .map(|builtin| (builtin.id(), builtin.arity))
+        .collect()
+}
+
+/// Parameters that the uncurried entry points of non-strict builtins take as
+/// thunks.
+pub fn lazy_params() -> HashMap<String, Vec<usize>> {
+    BUILTINS
+        .iter()
+        .filter(|builtin| !builtin.lazy.is_empty())
+        .map(|builtin| (builtin.id(), builtin.lazy.to_vec()))
+        .collect()
 }
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{node, purec, purec_with_input, scratch, stdout, STD};
+use std::fs;
+
+/// Every example survives the trip through JSON: it is exported again the
+/// same, comments included, and means the same to the evaluator and the JS
+/// backend.
+#[test]
+fn examples_round_trip() {
+    let dir = scratch("ast-json");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/pure");
+    for entry in fs::read_dir(examples).unwrap() {
+        let path = entry.unwrap().path();
+        if path.ends_with("unknown_references.pure") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        let pure = format!("{}.pure", name);
+        let json = format!("{}.json", name);
+        fs::copy(&path, dir.join(&pure)).unwrap();
+
+        let exported = stdout(&purec(&dir, &["emit", "ast-json", &pure]));
+        fs::write(dir.join(&json), &exported).unwrap();
+
+        assert_eq!(
+            stdout(&purec(&dir, &["emit", "ast-json", &json])),
+            exported,
+            "{}",
+            name
+        );
+        let expected = stdout(&purec(&dir, &["eval", &pure]));
+        assert_eq!(stdout(&purec(&dir, &["eval", &json])), expected);
+        // Deep mutual recursion overflows the stack without trampoline.
+        if name != "tail_calls" {
+            stdout(&purec(&dir, &["build", &json]));
+            let js = format!("{}.js", name);
+            assert_eq!(stdout(&node(&dir, &js)), expected);
+        }
+    }
+}
+
+#[test]
+fn comments_are_exported() {
+    let dir = scratch("ast-json-comments");
+    fs::write(
+        dir.join("main.pure"),
+        "{- One -}\nmain.main := {- two -} std.prints 2;\n",
+    )
+    .unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&stdout(&purec(
+        &dir,
+        &["emit", "ast-json", "main.pure"],
+    )))
+    .unwrap();
+
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["comments"][0]["text"], "{- One -}");
+    assert_eq!(json["comments"][1]["text"], "{- two -}");
+    assert_eq!(json["comments"][1]["span"]["start"]["line"], 2);
+    assert_eq!(json["comments"][1]["span"]["start"]["column"], 14);
+}
+
+/// Tools that generate programs have no source, so spans may be left out.
+#[test]
+fn generated_programs_run() {
+    let dir = scratch("ast-json-generated");
+    let program = r#"{
+        "version": 1,
+        "fixities": [],
+        "declarations": [
+            {"id": "main.main", "expr": {
+                "kind": "Call",
+                "func": {"kind": "ID", "id": "std.prints"},
+                "args": [{"kind": "Call",
+                    "func": {"kind": "ID", "id": "main.double"},
+                    "args": [{"kind": "Int", "value": 21}]}]
+            }},
+            {"id": "main.double", "expr": {
+                "kind": "Func",
+                "params": ["x"],
+                "body": {"kind": "Call",
+                    "func": {"kind": "ID", "id": "std.add"},
+                    "args": [{"kind": "Name", "name": "x"},
+                             {"kind": "Name", "name": "x"}]}
+            }}
+        ]
+    }"#;
+
+    let output = purec_with_input(&dir, &["eval", "-", "--ast-json"], program);
+
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn invalid_programs_are_rejected_with_their_place() {
+    let dir = scratch("ast-json-invalid");
+    let cases = [
+        (
+            r#"{"version": 2}"#,
+            "Unsupported AST JSON version 2, expected 1",
+        ),
+        (
+            r#"{"version": 1, "fixities": []}"#,
+            "Missing \"declarations\"",
+        ),
+        (
+            r#"{"version": 1, "fixities": [], "declarations": [
+                {"id": "main.main", "expr": {"kind": "Call",
+                    "func": {"kind": "ID", "id": "std.prints"},
+                    "args": [{"kind": "Int", "value": 4294967296}]}}]}"#,
+            "declarations[0].expr.args[0]: 4294967296 does not fit in 32 bits",
+        ),
+        (
+            r#"{"version": 1, "fixities": [], "declarations": [
+                {"id": "main", "expr": {"kind": "Int", "value": 1}}]}"#,
+            "declarations[0]: invalid id \"main\"",
+        ),
+        (
+            r#"{"version": 1, "fixities": [], "declarations": [
+                {"id": "main.main", "expr": {"kind": "ID", "id": "main.x"}}]}"#,
+            "Unknown references found: main.x",
+        ),
+        (
+            r#"{"version": 1, "declarations": [], "fixities": [
+                {"operator": "+", "assoc": "infixl", "precedence": 6},
+                {"operator": "+", "assoc": "infixr", "precedence": 6}]}"#,
+            "fixities[1]: fixity of + is declared twice",
+        ),
+    ];
+
+    for (program, error) in cases {
+        let output =
+            purec_with_input(&dir, &["check", "-", "--ast-json"], program);
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+    }
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{node, purec, scratch, stdout};
+use std::fs;
+
+/// A bundle runs on its own, without std.js next to it, and carries only the
+/// builtins the program uses and what they rely on.
+#[test]
+fn bundle_runs_without_the_runtime() {
+    let dir = scratch("bundle");
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (std.div (std.add 40 4) 1);",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "--bundle"]));
+    let js = fs::read_to_string(dir.join("main.js")).unwrap();
+
+    assert!(!dir.join("std.js").exists());
+    assert!(!js.contains("require("), "{}", js);
+    assert!(js.contains("const add$2 ="), "{}", js);
+    // std.div panics on division by zero.
+    assert!(js.contains("const Panic ="), "{}", js);
+    assert!(!js.contains("const mul$2 ="), "{}", js);
+    assert_eq!(stdout(&node(&dir, "main.js")), "44\n");
+}
+
+/// Namespaces named like the globals the runtime uses don't shadow them.
+#[test]
+fn esm_bundle_runs_with_namespaces_named_like_globals() {
+    let dir = scratch("bundle-esm");
+    fs::write(
+        dir.join("main.pure"),
+        "console.log := x -> std.add x 1;
+         process.exit := 1;
+         main.main := std.prints (console.log (std.div 41 process.exit));",
+    )
+    .unwrap();
+
+    stdout(&purec(
+        &dir,
+        &[
+            "build",
+            "main.pure",
+            "--module",
+            "esm",
+            "--bundle",
+            "-o",
+            "main.mjs",
+        ],
+    ));
+    fs::write(
+        dir.join("use.mjs"),
+        "import { console as c } from \"./main.mjs\";
+         console.log(c.log(1));",
+    )
+    .unwrap();
+
+    assert_eq!(stdout(&node(&dir, "main.mjs")), "42\n");
+    assert_eq!(stdout(&node(&dir, "use.mjs")), "42\n2\n");
+}
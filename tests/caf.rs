@@ -0,0 +1,94 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// Wraps the std runtime so that it counts multiplications and reports the
+/// count on exit.
+const COUNTING_STD: &str = r#"
+const std = require("./runtime.js");
+let count = 0;
+const mul$2 = (x, y) => {
+  count++;
+  return std.mul$2(x, y);
+};
+const mul = (x) => (y) => mul$2(x, y);
+process.on("exit", () => console.log(`mul: ${count}`));
+module.exports = { ...std, mul, mul$2 };
+"#;
+
+#[test]
+fn constants_are_computed_once() {
+    let dir = scratch("caf");
+    fs::copy(STD, dir.join("runtime.js")).unwrap();
+    fs::write(dir.join("std.js"), COUNTING_STD).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.big := std.mul 6 7;
+         main.double := x -> std.add x x;
+         main.main := std.prints (std.add (main.double main.big) main.big);",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "-o", "main.js"]));
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "126\nmul: 1\n");
+}
+
+#[test]
+fn constants_are_computed_on_first_use() {
+    let dir = scratch("caf-lazy");
+    fs::copy(STD, dir.join("runtime.js")).unwrap();
+    fs::write(dir.join("std.js"), COUNTING_STD).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.big := std.mul 6 7;
+         main.main := std.prints (std.iff 1 1 main.big);",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "-o", "main.js"]));
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "1\nmul: 0\n");
+}
+
+/// Aliases may refer to declarations further down, however many steps away.
+#[test]
+fn aliases_may_refer_forward() {
+    let dir = scratch("caf-forward");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints main.a;
+         main.a := main.c;
+         main.c := main.e;
+         main.e := 3;",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+    let js = fs::read_to_string(dir.join("main.js")).unwrap();
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "3\n");
+    assert_eq!(stdout(&purec(&dir, &["build", "main.pure", "-o", "-"])), js);
+}
+
+/// A library exports the value of a constant, not the cell that holds it.
+#[test]
+fn exported_constants_are_values() {
+    let dir = scratch("caf-export");
+    fs::copy(STD, dir.join("runtime.js")).unwrap();
+    fs::write(dir.join("std.js"), COUNTING_STD).unwrap();
+    fs::write(dir.join("main.pure"), "main.answer := std.mul 6 7;").unwrap();
+    fs::write(
+        dir.join("use.js"),
+        "const { main } = require(\"./main.js\");
+         console.log(\"loaded\");
+         console.log(main.answer, main.answer);",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "--lib"]));
+
+    assert_eq!(stdout(&node(&dir, "use.js")), "loaded\n42 42\nmul: 1\n");
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::{node, purec, purec_with_input, scratch, stdout, STD};
+use std::fs;
+
+const MAIN: &str = "main.main := std.prints (std.add 1 2);";
+
+#[test]
+fn build_derives_the_output_path_from_the_target() {
+    let dir = scratch("cli-derived");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(dir.join("main.pure"), MAIN).unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+    stdout(&purec(&dir, &["build", "main.pure", "--target", "vm"]));
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "3\n");
+    assert!(dir.join("main.bin").exists());
+}
+
+#[test]
+fn dash_reads_stdin_and_writes_stdout() {
+    let dir = scratch("cli-stdio");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+
+    let js = stdout(&purec_with_input(&dir, &["build", "-"], MAIN));
+    fs::write(dir.join("main.js"), &js).unwrap();
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "3\n");
+    assert_eq!(
+        js,
+        stdout(&purec_with_input(&dir, &["build", "-", "-o", "-"], MAIN))
+    );
+    assert_eq!(
+        stdout(&purec_with_input(
+            &dir,
+            &["fmt", "-"],
+            "main.main:=std.prints 1;"
+        )),
+        "main.main := std.prints 1;\n"
+    );
+}
+
+#[test]
+fn check_reports_errors_with_exit_codes() {
+    let dir = scratch("cli-check");
+    fs::write(dir.join("main.pure"), MAIN).unwrap();
+    fs::write(dir.join("lib.pure"), "lib.one := 1;").unwrap();
+
+    let ok = purec(&dir, &["check", "main.pure"]);
+    let no_main = purec(&dir, &["check", "lib.pure"]);
+    let missing = purec(&dir, &["check", "none.pure"]);
+    let usage = purec(&dir, &["check"]);
+
+    assert!(ok.status.success());
+    assert!(ok.stdout.is_empty());
+    assert!(stdout(&purec(&dir, &["check", "lib.pure", "--lib"])).is_empty());
+    assert_eq!(no_main.status.code(), Some(1));
+    assert_eq!(missing.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("none.pure"));
+    assert_eq!(usage.status.code(), Some(2));
+}
+
+#[test]
+fn run_exits_with_the_program_status() {
+    let dir = scratch("cli-run");
+    fs::write(dir.join("main.pure"), MAIN).unwrap();
+    fs::write(
+        dir.join("panic.pure"),
+        "main.main := std.prints (std.div 1 0);",
+    )
+    .unwrap();
+
+    assert_eq!(stdout(&purec(&dir, &["run", "main.pure"])), "3\n");
+    assert_eq!(purec(&dir, &["run", "panic.pure"]).status.code(), Some(101));
+}
+
+#[test]
+fn check_rejects_a_fixity_declared_twice() {
+    let dir = scratch("cli-fixity");
+    fs::write(
+        dir.join("main.pure"),
+        "infixl 6 +; infixr 6 +; (+) := std.add; main.main := std.prints 1;",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["check", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Fixity of + is declared twice"));
+}
+
+#[test]
+fn vm_target_reports_lambdas_it_cannot_compile() {
+    let dir = scratch("cli-vm-lambda");
+    fs::write(
+        dir.join("main.pure"),
+        "main.apply := f -> x -> f x;
+         main.main := std.prints (main.apply (x -> std.add x 1) 2);",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["build", "main.pure", "--target", "vm"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Lambdas are not supported by the VM target yet in main.main\n"
+    );
+    assert!(!dir.join("main.bin").exists());
+}
+
+#[test]
+fn check_rejects_declarations_in_the_std_namespace() {
+    let dir = scratch("cli-std");
+    fs::write(
+        dir.join("main.pure"),
+        "std.add := x -> y -> x; std.mine := 1;
+         main.main := std.prints (std.add 1 2);",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["check", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Declarations in the std namespace are reserved for builtins: \
+         std.add, std.mine"
+    ));
+}
+
+#[test]
+fn check_rejects_cyclic_definitions() {
+    let dir = scratch("cli-cyclic");
+    for (source, id) in [
+        ("main.a := main.b; main.b := main.a;", "main.a"),
+        ("main.a := std.add main.b 1; main.b := main.a;", "main.a"),
+        ("main.c := std.add main.c 1;", "main.c"),
+    ] {
+        fs::write(
+            dir.join("main.pure"),
+            format!("{} main.main := std.prints 0;", source),
+        )
+        .unwrap();
+
+        let output = purec(&dir, &["check", "main.pure"]);
+
+        assert_eq!(output.status.code(), Some(1), "{}", source);
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains(&format!("Cyclic definition of {}", id)));
+    }
+
+    // Lambdas and the branches of `std.iff` are only evaluated when needed.
+    fs::write(
+        dir.join("main.pure"),
+        "main.f := x -> main.g x; main.g := main.f;
+         main.a := std.iff 1 2 main.a;
+         main.main := std.prints main.a;",
+    )
+    .unwrap();
+    assert_eq!(stdout(&purec(&dir, &["run", "main.pure"])), "2\n");
+}
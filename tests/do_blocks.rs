@@ -0,0 +1,30 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// Statements run in order and once each, binds see the results of earlier
+/// commands, later binds shadow earlier ones and `pure` gives the block's
+/// result.
+#[test]
+fn do_blocks_run_their_statements_in_order() {
+    let dir = scratch("do-blocks");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.twice := c -> do { x <- c; y <- c; pure (std.add x y) };
+main.main := do {
+  a <- std.cmd 20;
+  b <- main.twice (std.prints 11);
+  std.prints (std.add a b);
+  a <- std.cmd 1;
+  std.prints a
+};
+",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "11\n11\n42\n1\n");
+}
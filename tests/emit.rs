@@ -0,0 +1,192 @@
+mod common;
+
+use common::{purec, scratch, stdout};
+use std::fs;
+
+/// The entrypoint and one other declaration, so that the VM code is laid out
+/// in a known order.
+const SOURCE: &str = "main.main := std.prints (std.add main.two 3);
+main.two := std.mul 1 2;
+";
+
+fn emit(kind: &str) -> String {
+    let dir = scratch(&format!("emit-{}", kind));
+    fs::write(dir.join("main.pure"), SOURCE).unwrap();
+    stdout(&purec(&dir, &["emit", kind, "main.pure"]))
+}
+
+#[test]
+fn ast_is_a_tree_with_spans() {
+    assert_eq!(
+        emit("ast"),
+        "main.main 1:1-1:46
+  Call 1:14-1:45
+    ID std.prints 1:14-1:24
+    Call 1:26-1:44
+      ID std.add 1:26-1:33
+      ID main.two 1:34-1:42
+      Int 3 1:43-1:44
+main.two 2:1-2:25
+  Call 2:13-2:24
+    ID std.mul 2:13-2:20
+    Int 1 2:21-2:22
+    Int 2 2:23-2:24
+"
+    );
+}
+
+#[test]
+fn ast_json_has_every_node() {
+    let ast: serde_json::Value =
+        serde_json::from_str(&emit("ast-json")).unwrap();
+    let two = &ast["declarations"][1];
+
+    assert_eq!(two["id"], "main.two");
+    assert_eq!(two["expr"]["kind"], "Call");
+    assert_eq!(two["expr"]["func"]["id"], "std.mul");
+    assert_eq!(two["expr"]["args"][1]["value"], 2);
+    assert_eq!(two["span"]["start"]["line"], 2);
+}
+
+#[test]
+fn def_refers_to_definitions_by_name_and_offset() {
+    assert_eq!(
+        emit("def"),
+        "main.main @0
+  ARGC 0
+  PUSH_FN std.prints ; id 6
+  PUSH_FN std.add ; id 2
+  PUSH_CAF main.two ; @64
+  PUSH_I32 3
+  FEED 2
+  FEED 1
+  RETURN
+main.two @64
+  ARGC 0
+  PUSH_FN std.mul ; id 4
+  PUSH_I32 1
+  PUSH_I32 2
+  FEED 2
+  RETURN
+"
+    );
+}
+
+#[test]
+fn asm_is_laid_out_as_in_the_image() {
+    let asm = emit("asm");
+
+    assert!(asm.starts_with(
+        "version 1
+import 2 std.add
+import 4 std.mul
+import 6 std.prints
+data 0 bytes
+0000 ARGC 0
+0008 PUSH_FN 6 ; std.prints
+"
+    ));
+    assert!(asm.contains("0024 PUSH_CAF 64\n"));
+    assert!(asm.ends_with(
+        "0064 ARGC 0
+0072 PUSH_FN 4 ; std.mul
+0080 PUSH_I32 1
+0088 PUSH_I32 2
+0096 FEED 2
+0104 RETURN
+"
+    ));
+}
+
+#[test]
+fn def_passes_parameters_as_arguments() {
+    let dir = scratch("emit-function");
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (main.double 21);
+main.double := x -> std.add x x;
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        stdout(&purec(&dir, &["emit", "def", "main.pure"])),
+        "main.main @0
+  ARGC 0
+  PUSH_FN std.prints ; id 6
+  PUSH_CMD main.double ; @56
+  PUSH_I32 21
+  FEED 1
+  FEED 1
+  RETURN
+main.double @56
+  ARGC 1
+  PUSH_FN std.add ; id 2
+  PUSH_ARG 0
+  PUSH_ARG 0
+  FEED 2
+  RETURN
+"
+    );
+}
+
+/// Lambdas inside expressions, here an operator section, have no VM code
+/// yet, which the VM dumps report instead of printing.
+#[test]
+fn vm_dumps_reject_lambdas_in_expressions() {
+    let dir = scratch("emit-lambda");
+    fs::write(
+        dir.join("main.pure"),
+        "(+) := std.add;
+main.main := std.prints (main.apply (+ 1) 2);
+main.apply := f -> x -> f x;
+",
+    )
+    .unwrap();
+
+    for args in [
+        &["emit", "def", "main.pure"][..],
+        &["emit", "asm", "main.pure"],
+        &["build", "main.pure", "--emit", "def,asm"],
+    ] {
+        let output = purec(&dir, args);
+
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "Lambdas are not supported by the VM target yet in main.main\n"
+        );
+    }
+    assert!(
+        stdout(&purec(&dir, &["emit", "js", "main.pure"])).contains("Func $0")
+    );
+}
+
+#[test]
+fn js_is_the_tree_before_printing() {
+    let js = emit("js");
+
+    assert!(js.starts_with("Assign const std\n  Call\n    Name require\n"));
+    assert!(js.contains("Mapped 2:1-2:25\n  Assign const main$two\n"));
+}
+
+#[test]
+fn build_writes_dumps_next_to_the_output() {
+    let dir = scratch("emit-build");
+    fs::write(dir.join("main.pure"), SOURCE).unwrap();
+
+    stdout(&purec(
+        &dir,
+        &["build", "main.pure", "--target", "vm", "--emit", "ast,asm"],
+    ));
+
+    assert!(dir.join("main.bin").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join("main.bin.ast")).unwrap(),
+        stdout(&purec(&dir, &["emit", "ast", "main.pure"]))
+    );
+    assert!(fs::read_to_string(dir.join("main.bin.asm"))
+        .unwrap()
+        .starts_with("version 1\n"));
+    assert!(!dir.join("main.bin.def").exists());
+}
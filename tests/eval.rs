@@ -0,0 +1,130 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// The evaluator is the reference: every example must print the same when
+/// it is interpreted and when it is compiled to JS, with or without the
+/// trampoline.
+#[test]
+fn js_backend_agrees_with_evaluator() {
+    let dir = scratch("eval");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/pure");
+    let mut programs: Vec<(String, String)> = fs::read_dir(examples)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| !path.ends_with("unknown_references.pure"))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            (name, fs::read_to_string(path).unwrap())
+        })
+        .collect();
+    programs.push((
+        "lazy_iff".to_string(),
+        "main.main := std.prints (std.iff 1 5 (std.div 1 0));".to_string(),
+    ));
+    programs.push((
+        "lazy_iff_nested".to_string(),
+        "main.main := std.prints ((std.iff 1 5) (std.div 1 0));".to_string(),
+    ));
+
+    for (name, source) in programs {
+        let file = format!("{}.pure", name);
+        fs::write(dir.join(&file), source).unwrap();
+        let expected = stdout(&purec(&dir, &["eval", &file]));
+        assert!(!expected.is_empty(), "{} prints nothing", name);
+
+        let flags: &[&[&str]] = match name.as_str() {
+            // Deep mutual recursion overflows the stack without trampoline.
+            "tail_calls" => &[&["--trampoline"]],
+            _ => &[&[], &["--trampoline"]],
+        };
+        for flags in flags {
+            let js = format!("{}.js", name);
+            let mut args = vec!["build", file.as_str(), "-o", &js];
+            args.extend_from_slice(flags);
+            stdout(&purec(&dir, &args));
+            assert_eq!(
+                stdout(&node(&dir, &js)),
+                expected,
+                "{} {:?}",
+                name,
+                flags
+            );
+        }
+    }
+}
+
+#[test]
+fn evaluator_panics_like_the_backends() {
+    let dir = scratch("eval-panic");
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (std.div 1 (std.sub 1 1));",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["eval", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(101));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "panic in std.div: division by zero\n"
+    );
+}
+
+#[test]
+fn cyclic_definitions_are_reported() {
+    let dir = scratch("eval-cycle");
+    fs::write(
+        dir.join("main.pure"),
+        "main.a := main.f 1;
+         main.f := x -> std.add main.a x;
+         main.main := std.prints main.a;",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["eval", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("cyclic definition of main.a"));
+}
+
+/// Each command here makes the next one as it runs, a hundred thousand times
+/// over.
+#[test]
+fn long_command_chains_run_in_constant_stack_space() {
+    let dir = scratch("eval-commands");
+    fs::write(
+        dir.join("main.pure"),
+        "main.count := n -> std.chainCmd (std.cmd n)
+           (x -> std.iff x (main.count (std.sub x 1)) (std.prints x));
+         main.main := std.thenCmd (main.count 100000) (std.prints 1);",
+    )
+    .unwrap();
+
+    assert_eq!(stdout(&purec(&dir, &["eval", "main.pure"])), "0\n1\n");
+}
+
+#[test]
+fn deep_recursion_is_reported() {
+    let dir = scratch("eval-depth");
+    let source = |n| {
+        format!(
+            "main.sum := n -> std.iff n (std.add n (main.sum (std.sub n 1))) 0;
+             main.main := std.prints (main.sum {});",
+            n
+        )
+    };
+    fs::write(dir.join("main.pure"), source(10000)).unwrap();
+    assert_eq!(stdout(&purec(&dir, &["eval", "main.pure"])), "50005000\n");
+
+    fs::write(dir.join("main.pure"), source(1000000)).unwrap();
+    let output = purec(&dir, &["eval", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("stack overflow: evaluation nested more than 100000 deep"));
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{purec, scratch, stdout};
+use std::fs;
+
+const MESSY: &str = "\
+{- A messy file. -}
+infixl   6 +;   infixl 1 >>;
+(+):=std.add;  {- trailing -}
+(>>) := std.thenCmd;
+
+
+{- Two blank lines above. -}
+main.main:=std.prints ((main.f) 1) >> std.prints (main.g (+ 1) ((x -> x) 2)) >> std.prints ((((1 + 2)))) >> main.h;
+main.f := x->y->x+y;
+main.g := f -> x -> f (f ((f) x));
+main.h:=do{x<-std.cmd 1;std.prints x;pure(std.add x 1)};
+main.k := std.add {- inner -}   1;
+";
+
+const FORMATTED: &str = "\
+{- A messy file. -}
+infixl 6 +;
+infixl 1 >>;
+(+) := std.add; {- trailing -}
+(>>) := std.thenCmd;
+
+{- Two blank lines above. -}
+main.main :=
+  std.prints (main.f 1)
+  >> std.prints (main.g (+ 1) ((x -> x) 2))
+  >> std.prints (1 + 2)
+  >> main.h;
+main.f := x -> y -> x + y;
+main.g := f -> x -> f (f (f x));
+main.h := do { x <- std.cmd 1; std.prints x; pure (std.add x 1) };
+main.k := std.add {- inner -}   1;
+";
+
+#[test]
+fn formats_in_canonical_style() {
+    let dir = scratch("fmt");
+    fs::write(dir.join("main.pure"), MESSY).unwrap();
+
+    let check = purec(&dir, &["fmt", "--check", "main.pure"]);
+    assert_eq!(check.status.code(), Some(1));
+    assert_eq!(fs::read_to_string(dir.join("main.pure")).unwrap(), MESSY);
+
+    stdout(&purec(&dir, &["fmt", "main.pure"]));
+    assert_eq!(
+        fs::read_to_string(dir.join("main.pure")).unwrap(),
+        FORMATTED
+    );
+    stdout(&purec(&dir, &["fmt", "--check", "main.pure"]));
+}
+
+/// Every example is already formatted, and formatting a file changes neither
+/// what it prints nor the code it compiles to, up to the order of the
+/// declarations, which isn't fixed.
+#[test]
+fn formatting_is_idempotent_and_preserves_semantics() {
+    let dir = scratch("fmt-examples");
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/pure");
+    for entry in fs::read_dir(examples).unwrap() {
+        let path = entry.unwrap().path();
+        if path.ends_with("unknown_references.pure") {
+            continue;
+        }
+        let file = path.file_name().unwrap().to_str().unwrap();
+        fs::copy(&path, dir.join(file)).unwrap();
+        stdout(&purec(&dir, &["fmt", "--check", file]));
+    }
+
+    let compiled = |js: &str| {
+        stdout(&purec(&dir, &["build", "messy.pure", "-o", js]));
+        let code = fs::read_to_string(dir.join(js)).unwrap();
+        let mut lines: Vec<String> = code.lines().map(String::from).collect();
+        lines.sort();
+        lines
+    };
+    fs::write(dir.join("messy.pure"), MESSY).unwrap();
+    let printed = stdout(&purec(&dir, &["eval", "messy.pure"]));
+    let before = compiled("before.js");
+    stdout(&purec(&dir, &["fmt", "messy.pure"]));
+    assert_eq!(stdout(&purec(&dir, &["eval", "messy.pure"])), printed);
+    assert_eq!(compiled("after.js"), before);
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// The arm that `std.iff` doesn't pick would panic if it were evaluated:
+/// called directly, through an alias, and in tail position.
+#[test]
+fn only_the_chosen_arm_is_evaluated() {
+    let dir = scratch("iff");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.if := std.iff;
+main.safeDiv := x -> y -> std.iff y (std.div x y) 0;
+main.main := do {
+  std.prints (std.iff 1 5 (std.div 1 0));
+  std.prints (std.iff 0 (std.div 1 0) 6);
+  std.prints (main.if 1 7 (std.div 1 0));
+  std.prints (main.safeDiv 16 2);
+  std.prints (main.safeDiv 1 0)
+};
+",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+
+    assert_eq!(stdout(&node(&dir, "main.js")), "5\n6\n7\n8\n0\n");
+}
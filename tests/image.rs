@@ -0,0 +1,42 @@
+mod common;
+
+use common::{purec, scratch, stdout};
+use std::fs;
+
+fn u32_at(image: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn image_starts_with_version_and_imported_builtins() {
+    let dir = scratch("image");
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (std.add main.two (std.mul 2 2));
+         main.two := std.add 1 1;",
+    )
+    .unwrap();
+
+    stdout(&purec(
+        &dir,
+        &["build", "main.pure", "--target", "vm", "-o", "main.bin"],
+    ));
+    let image = fs::read(dir.join("main.bin")).unwrap();
+
+    assert_eq!(u32_at(&image, 0), 1);
+    let count = u32_at(&image, 4) as usize;
+    let mut imports = vec![];
+    let mut offset = 8;
+    for _ in 0..count {
+        let id = u32_at(&image, offset);
+        let length = u32_at(&image, offset + 4) as usize;
+        let name = &image[offset + 8..offset + 8 + length];
+        imports.push((id, String::from_utf8(name.to_vec()).unwrap()));
+        offset += 8 + length.div_ceil(8) * 8;
+    }
+    assert_eq!(
+        imports,
+        [(2, "std.add"), (4, "std.mul"), (6, "std.prints")]
+            .map(|(id, name)| (id, name.to_string()))
+    );
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+type Builtin = fn(i32, i32) -> i32;
+
+/// Edge cases of the I32 builtins with the results that Rust's wrapping
+/// arithmetic gives, which the JS runtime must agree with.
+fn cases() -> Vec<(&'static str, i32, i32, i32)> {
+    let cases: Vec<(&str, Builtin, i32, i32)> = vec![
+        ("add", i32::wrapping_add, i32::MAX, 1),
+        ("add", i32::wrapping_add, i32::MIN, -1),
+        ("add", i32::wrapping_add, i32::MAX, i32::MAX),
+        ("add", i32::wrapping_add, -5, 3),
+        ("sub", i32::wrapping_sub, i32::MIN, 1),
+        ("sub", i32::wrapping_sub, i32::MAX, -1),
+        ("sub", i32::wrapping_sub, 0, i32::MIN),
+        ("mul", i32::wrapping_mul, i32::MAX, 2),
+        ("mul", i32::wrapping_mul, 65536, 65536),
+        ("mul", i32::wrapping_mul, i32::MIN, -1),
+        ("mul", i32::wrapping_mul, 123456789, 987654321),
+        ("mul", i32::wrapping_mul, -7, 6),
+        ("div", i32::wrapping_div, 7, 2),
+        ("div", i32::wrapping_div, -7, 2),
+        ("div", i32::wrapping_div, 7, -2),
+        ("div", i32::wrapping_div, -7, -2),
+        ("div", i32::wrapping_div, 1, 3),
+        ("div", i32::wrapping_div, i32::MIN, -1),
+    ];
+    cases
+        .into_iter()
+        .map(|(op, f, x, y)| (op, x, y, f(x, y)))
+        .collect()
+}
+
+/// Every case is computed twice: saturated, through the uncurried builtin,
+/// and by applying the curried builtin one argument at a time.
+#[test]
+fn i32_arithmetic_wraps_around() {
+    let dir = scratch("integers");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    let mut source = String::from("main.apply := f -> x -> y -> f x y;\n");
+    for (i, (op, x, y, _)) in cases().iter().enumerate() {
+        source.push_str(&format!("main.u{} := std.{} {} {};\n", i, op, x, y));
+        source.push_str(&format!(
+            "main.c{} := main.apply std.{} {} {};\n",
+            i, op, x, y
+        ));
+    }
+    fs::write(dir.join("cases.pure"), source).unwrap();
+    let driver = format!(
+        "const {{ main }} = require(\"./cases.js\");
+         for (let i = 0; i < {}; i++) {{
+           console.log(main[`u${{i}}`], main[`c${{i}}`]);
+         }}",
+        cases().len()
+    );
+    fs::write(dir.join("driver.js"), driver).unwrap();
+
+    stdout(&purec(
+        &dir,
+        &["build", "cases.pure", "--lib", "-o", "cases.js"],
+    ));
+    let output = stdout(&node(&dir, "driver.js"));
+
+    for ((op, x, y, expected), line) in cases().iter().zip(output.lines()) {
+        assert_eq!(
+            line,
+            format!("{} {}", expected, expected),
+            "std.{} {} {}",
+            op,
+            x,
+            y
+        );
+    }
+    assert_eq!(output.lines().count(), cases().len());
+}
+
+#[test]
+fn out_of_range_literals_are_rejected() {
+    let dir = scratch("integers-range");
+    fs::write(dir.join("main.pure"), "main.main := std.prints 2147483648;")
+        .unwrap();
+
+    let output = purec(&dir, &["check", "main.pure"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Integer literal 2147483648 at 1:25 does not fit in 32 bits"
+    ));
+}
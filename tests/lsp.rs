@@ -0,0 +1,171 @@
+mod common;
+
+use common::{purec_with_input, scratch};
+use serde_json::{json, Value};
+
+const URI: &str = "file:///main.pure";
+
+const SOURCE: &str = "\
+infixl 6 +;
+(+) := std.add;
+main.main := std.prints (main.double 21);
+main.double := x -> x + x;
+";
+
+fn frame(message: Value) -> String {
+    let content = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+}
+
+fn request(id: u32, method: &str, params: Value) -> String {
+    frame(
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}),
+    )
+}
+
+fn notification(method: &str, params: Value) -> String {
+    frame(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+}
+
+fn at(line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": {"uri": URI},
+        "position": {"line": line, "character": character},
+    })
+}
+
+fn change(text: &str) -> String {
+    notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 2},
+            "contentChanges": [{"text": text}],
+        }),
+    )
+}
+
+fn messages(mut output: &str) -> Vec<Value> {
+    let mut messages = vec![];
+    while let Some(rest) = output.strip_prefix("Content-Length: ") {
+        let (length, rest) = rest.split_once("\r\n\r\n").unwrap();
+        let (content, rest) = rest.split_at(length.parse().unwrap());
+        messages.push(serde_json::from_str(content).unwrap());
+        output = rest;
+    }
+    assert!(output.is_empty(), "Unframed output: {}", output);
+    messages
+}
+
+fn range(line: u32, start: u32, end: u32) -> Value {
+    json!({
+        "start": {"line": line, "character": start},
+        "end": {"line": line, "character": end},
+    })
+}
+
+#[test]
+fn scripted_session() {
+    let dir = scratch("lsp");
+    let mut references = at(2, 26);
+    references["context"] = json!({"includeDeclaration": true});
+    let input = [
+        request(1, "initialize", json!({"capabilities": {}})),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({"textDocument": {
+                "uri": URI,
+                "languageId": "pure",
+                "version": 1,
+                "text": SOURCE,
+            }}),
+        ),
+        request(2, "textDocument/definition", at(2, 26)),
+        request(3, "textDocument/references", references),
+        request(4, "textDocument/hover", at(2, 15)),
+        request(5, "textDocument/hover", at(3, 22)),
+        request(6, "textDocument/completion", at(3, 0)),
+        request(
+            7,
+            "textDocument/documentSymbol",
+            json!({"textDocument": {"uri": URI}}),
+        ),
+        change(&SOURCE.replace("x + x", "x + y")),
+        change("main.main := ;"),
+        request(8, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]
+    .concat();
+
+    let output = purec_with_input(&dir, &["lsp"], &input);
+    assert_eq!(output.status.code(), Some(0));
+    let messages = messages(&String::from_utf8(output.stdout).unwrap());
+    assert_eq!(messages.len(), 11);
+
+    let capabilities = &messages[0]["result"]["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+
+    assert_eq!(messages[1]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(messages[1]["params"]["diagnostics"], json!([]));
+
+    assert_eq!(messages[2]["id"], 2);
+    assert_eq!(
+        messages[2]["result"],
+        json!({"uri": URI, "range": range(3, 0, 11)})
+    );
+
+    assert_eq!(
+        messages[3]["result"],
+        json!([
+            {"uri": URI, "range": range(3, 0, 11)},
+            {"uri": URI, "range": range(2, 25, 36)},
+        ])
+    );
+
+    assert_eq!(
+        messages[4]["result"]["contents"]["value"],
+        "```pure\nstd.prints :: Str -> Cmd Str\n```"
+    );
+    assert_eq!(
+        messages[5]["result"]["contents"]["value"],
+        "```pure\n(+) := std.add;\n```"
+    );
+
+    let labels: Vec<&str> = messages[6]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert!(labels.contains(&"main.double"));
+    assert!(labels.contains(&"std.chainCmd"));
+
+    let symbols: Vec<(&str, u64)> = messages[7]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| {
+            (
+                symbol["name"].as_str().unwrap(),
+                symbol["kind"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(symbols, [("+", 14), ("main.main", 14), ("main.double", 12)]);
+    assert_eq!(messages[7]["result"][0]["selectionRange"], range(1, 0, 3));
+
+    let diagnostics = &messages[8]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["message"], "Unbound name: y");
+    assert_eq!(diagnostics[0]["range"], range(3, 24, 25));
+
+    let diagnostics = &messages[9]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["range"], range(0, 13, 13));
+
+    assert_eq!(
+        messages[10],
+        json!({"jsonrpc": "2.0", "id": 8, "result": null})
+    );
+}
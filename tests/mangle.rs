@@ -0,0 +1,54 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// Reserved words, operators and dotted ids, including ones that would clash
+/// if they were emitted naively: `main.a` next to `main.a.b`, the operators
+/// `<*>` and `<` `*` `>` spelled one after the other, and parameters named
+/// after JavaScript keywords and the `std` runtime binding.
+const SOURCE: &str = "infixl 4 <*>;
+infixl 4 <;
+infixl 4 *>;
+
+(<*>) := x -> y -> std.add x y;
+(<) := x -> y -> std.sub x y;
+(*>) := x -> y -> std.mul x y;
+
+main.a := 1;
+main.a.b := 2;
+main.if := new -> this -> std -> std.add new (std.mul this std);
+main.main := do {
+  std.prints main.a;
+  std.prints main.a.b;
+  std.prints (main.if 3 4 5);
+  std.prints (10 <*> 2);
+  std.prints (10 < 2);
+  std.prints (10 *> 2)
+};
+";
+
+#[test]
+fn mangled_names_do_not_collide() {
+    let dir = scratch("mangle");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(dir.join("main.pure"), SOURCE).unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+    let js = fs::read_to_string(dir.join("main.js")).unwrap();
+
+    for declaration in [
+        "function $lt$star$gt(",
+        "function $lt(",
+        "function $star$gt(",
+        "function main$if(",
+        "const main$a =",
+        "const main$a$b =",
+    ] {
+        assert!(js.contains(declaration), "{}\n{}", declaration, js);
+    }
+    for param in ["new$", "this$", "std$"] {
+        assert!(js.contains(param), "{}\n{}", param, js);
+    }
+    assert_eq!(stdout(&node(&dir, "main.js")), "1\n2\n23\n12\n8\n20\n");
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{purec, purec_with_input, scratch, stdout};
+use std::fs;
+
+/// Bare declarations and the references to them are qualified, while names
+/// bound by lambdas and do blocks, even when they shadow a declaration, and
+/// comments are left alone.
+#[test]
+fn migrate_qualifies_bare_declarations() {
+    let dir = scratch("migrate");
+    fs::write(
+        dir.join("main.pure"),
+        "{- double twice -}
+main := std.prints (quad 2);
+double := x -> std.add x x;
+quad := double -> main.twice double;
+main.twice := n -> do { x <- std.cmd n; double x };
+",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["migrate", "main.pure"]));
+
+    assert_eq!(
+        fs::read_to_string(dir.join("main.pure")).unwrap(),
+        "{- double twice -}
+main.main := std.prints (main.quad 2);
+main.double := x -> std.add x x;
+main.quad := double -> main.twice double;
+main.twice := n -> do { x <- std.cmd n; main.double x };
+"
+    );
+}
+
+#[test]
+fn migrate_uses_the_namespace_given() {
+    let dir = scratch("migrate-namespace");
+
+    let output = stdout(&purec_with_input(
+        &dir,
+        &["migrate", "-", "--namespace", "app.util"],
+        "one := 1; two := std.add one one;",
+    ));
+    let invalid = purec_with_input(
+        &dir,
+        &["migrate", "-", "--namespace", "1x"],
+        "one := 1;",
+    );
+
+    assert_eq!(
+        output,
+        "app.util.one := 1; app.util.two := std.add app.util.one app.util.one;"
+    );
+    assert_eq!(invalid.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&invalid.stderr)
+        .contains("Invalid namespace '1x'"));
+}
+
+/// Bare names may only be lambda parameters, so a declaration that hasn't
+/// been migrated is reported where it is used.
+#[test]
+fn check_rejects_unbound_names() {
+    let dir = scratch("migrate-unbound");
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (x -> std.add x y) 1 z;",
+    )
+    .unwrap();
+
+    let output = purec(&dir, &["check", "main.pure"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Unbound names found: y, z"));
+}
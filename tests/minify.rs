@@ -0,0 +1,36 @@
+mod common;
+
+use common::{node, purec, scratch, stdout};
+use std::fs;
+
+const SOURCE: &str = "main.sum := n -> acc ->
+  std.iff n (main.sum (std.sub n 1) (std.add acc n)) acc;
+main.main := std.prints (main.sum 100 0);
+";
+
+/// Minified, the bundled runtime is on one line with the generated code, and
+/// the loop variables of self tail calls get short names like parameters do.
+#[test]
+fn minified_bundle_is_one_line() {
+    let dir = scratch("minify");
+    fs::write(dir.join("main.pure"), SOURCE).unwrap();
+
+    stdout(&purec(
+        &dir,
+        &["build", "main.pure", "--bundle", "-o", "full.js"],
+    ));
+    stdout(&purec(
+        &dir,
+        &["build", "main.pure", "--bundle", "--minify", "-o", "min.js"],
+    ));
+    let full = fs::read_to_string(dir.join("full.js")).unwrap();
+    let min = fs::read_to_string(dir.join("min.js")).unwrap();
+
+    assert!(full.contains("\n  const add$2 = "), "{}", full);
+    assert_eq!(min.lines().count(), 1, "{}", min);
+    assert!(!min.contains("  "), "{}", min);
+    assert!(min.contains("(x+y)|0"), "{}", min);
+    assert!(min.contains("for(;;){const c=a;const d=b;"), "{}", min);
+    assert_eq!(stdout(&node(&dir, "min.js")), "5050\n");
+    assert_eq!(stdout(&node(&dir, "full.js")), "5050\n");
+}
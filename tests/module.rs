@@ -0,0 +1,60 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+const LIB: &str = "(+) := std.add;
+main.double := x -> x + x;
+main.two := 2;
+main.util.half := x -> std.div x 2;
+main._hidden := 1;
+";
+
+/// Prints what a consumer sees of the library, given how it loads it.
+const USE: &str = "console.log(
+  main.double(main.two),
+  main[\"util.half\"](10),
+  $plus(1)(2),
+  Object.keys(main).join()
+);
+";
+
+const EXPECTED: &str = "4 5 3 double,two,util.half\n";
+
+#[test]
+fn commonjs_exports_one_flat_object_per_namespace() {
+    let dir = scratch("module-cjs");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(dir.join("lib.pure"), LIB).unwrap();
+    fs::write(
+        dir.join("use.js"),
+        format!("const {{ main, $plus }} = require(\"./lib.js\");\n{}", USE),
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "lib.pure", "--lib"]));
+
+    assert_eq!(stdout(&node(&dir, "use.js")), EXPECTED);
+}
+
+#[test]
+fn esm_exports_one_flat_object_per_namespace() {
+    let dir = scratch("module-esm");
+    let esm = concat!(env!("CARGO_MANIFEST_DIR"), "/js/esm");
+    fs::copy(format!("{}/std.js", esm), dir.join("std.js")).unwrap();
+    fs::copy(format!("{}/package.json", esm), dir.join("package.json"))
+        .unwrap();
+    fs::write(dir.join("lib.pure"), LIB).unwrap();
+    fs::write(
+        dir.join("use.js"),
+        format!("import {{ main, $plus }} from \"./lib.js\";\n{}", USE),
+    )
+    .unwrap();
+
+    stdout(&purec(
+        &dir,
+        &["build", "lib.pure", "--lib", "--module", "esm"],
+    ));
+
+    assert_eq!(stdout(&node(&dir, "use.js")), EXPECTED);
+}
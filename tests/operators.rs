@@ -0,0 +1,130 @@
+mod common;
+
+use common::{purec, scratch, stdout};
+use std::fs;
+
+const OPERATORS: &str = "infixl 6 +;
+infixl 6 -;
+infixr 6 +^;
+infixl 7 *;
+infixr 5 ^-;
+infix 4 ==;
+(+) := std.add;
+(-) := std.sub;
+(+^) := std.add;
+(*) := std.mul;
+(^-) := std.sub;
+(==) := std.sub;
+";
+
+/// The AST of `expr`, without spans, one node per line.
+fn tree(name: &str, expr: &str) -> String {
+    let dir = scratch(&format!("operators-{}", name));
+    fs::write(
+        dir.join("main.pure"),
+        format!("{}main.x := {};\n", OPERATORS, expr),
+    )
+    .unwrap();
+    let ast = stdout(&purec(&dir, &["emit", "ast", "main.pure", "--lib"]));
+    ast.lines()
+        .skip_while(|line| !line.starts_with("main.x "))
+        .skip(1)
+        .map(|line| {
+            line.rsplit_once(' ').unwrap().0.strip_prefix("  ").unwrap()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn precedence_and_associativity_group_operands() {
+    assert_eq!(
+        tree("precedence", "1 + 2 * 3"),
+        "Call\n  ID +\n  Int 1\n  Call\n    ID *\n    Int 2\n    Int 3"
+    );
+    assert_eq!(
+        tree("left", "1 - 2 - 3"),
+        "Call\n  ID -\n  Call\n    ID -\n    Int 1\n    Int 2\n  Int 3"
+    );
+    assert_eq!(
+        tree("right", "1 ^- 2 ^- 3"),
+        "Call\n  ID ^-\n  Int 1\n  Call\n    ID ^-\n    Int 2\n    Int 3"
+    );
+    assert_eq!(
+        tree("mixed", "1 * 2 ^- 3 + 4"),
+        "Call\n  ID ^-\n  Call\n    ID *\n    Int 1\n    Int 2\n  \
+         Call\n    ID +\n    Int 3\n    Int 4"
+    );
+}
+
+/// A right section is a lambda of the reserved `$0`, a left section a
+/// partial application of the operator.
+#[test]
+fn sections_desugar() {
+    assert_eq!(
+        tree("right-section", "(- 1)"),
+        "Func $0\n  Call\n    ID -\n    Name $0\n    Int 1"
+    );
+    assert_eq!(tree("left-section", "(10 -)"), "Call\n  ID -\n  Int 10");
+}
+
+#[test]
+fn operators_evaluate_as_they_group() {
+    let dir = scratch("operators-eval");
+    fs::write(
+        dir.join("main.pure"),
+        format!(
+            "{}main.main := do {{
+  std.prints (1 + 2 * 3);
+  std.prints (10 - 3 - 2);
+  std.prints (10 ^- 3 ^- 2);
+  std.prints ((- 1) 5);
+  std.prints ((10 -) 3)
+}};
+",
+            OPERATORS
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(
+        stdout(&purec(&dir, &["eval", "main.pure"])),
+        "7\n5\n9\n4\n7\n"
+    );
+}
+
+#[test]
+fn operators_of_one_precedence_need_one_associativity() {
+    let dir = scratch("operators-mix");
+    for (expr, error) in [
+        (
+            "1 == 2 == 3",
+            "Cannot mix '==' and '==' of the same precedence 4",
+        ),
+        (
+            "1 + 2 +^ 3",
+            "Cannot mix '+' and '+^' of the same precedence 6",
+        ),
+    ] {
+        fs::write(
+            dir.join("main.pure"),
+            format!("{}main.main := std.prints ({});\n", OPERATORS, expr),
+        )
+        .unwrap();
+
+        let output = purec(&dir, &["check", "main.pure"]);
+
+        assert_eq!(output.status.code(), Some(1), "{}", expr);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(error),
+            "{}",
+            expr
+        );
+    }
+    fs::write(
+        dir.join("main.pure"),
+        format!("{}main.main := std.prints ((1 == 2) == 3);\n", OPERATORS),
+    )
+    .unwrap();
+    assert!(purec(&dir, &["check", "main.pure"]).status.success());
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+#[test]
+fn division_by_zero_panics() {
+    let dir = scratch("panic");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.main := std.prints (std.div 1 (std.sub 1 1));",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "-o", "main.js"]));
+    let output = node(&dir, "main.js");
+
+    assert_eq!(output.status.code(), Some(101));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "panic in std.div: division by zero\n"
+    );
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::{purec_with_input, scratch, stdout};
+
+#[test]
+fn repl_keeps_declarations_and_evaluates_expressions() {
+    let dir = scratch("repl");
+    let input = "\
+std.add
+infixl 6 +; (+) := std.add;
+main.double := x -> x + x;
+main.double 21
+main.double
+std.prints 5
+main.oops := y;
+std.div 1 0
+main.double (std.iff 0 (std.div 1 0) 2)
+";
+
+    let output = stdout(&purec_with_input(&dir, &["repl"], input));
+
+    assert_eq!(
+        output,
+        "\
+<function> :: I32 -> I32 -> I32
+42 :: I32
+<function>
+5
+5 :: I32
+Semantic error:
+Unbound names found: y
+panic in std.div: division by zero
+4 :: I32
+"
+    );
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::{purec, scratch, stdout, STD};
+use std::fs;
+use std::process::Command;
+
+/// Node reports an error in generated code at its place in the Pure source.
+#[test]
+fn errors_point_into_the_source() {
+    let dir = scratch("source-map");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "main.x := 5;\nmain.main := std.prints\n  (main.x 1);\n",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure", "--source-map"]));
+    let map: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(dir.join("main.js.map")).unwrap(),
+    )
+    .unwrap();
+    let output = Command::new("node")
+        .current_dir(&dir)
+        .args(["--enable-source-maps", "main.js"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(map["sources"][0], "main.pure");
+    assert!(stderr.contains("is not a function"), "{}", stderr);
+    assert!(stderr.contains("main.pure:3:4"), "{}", stderr);
+}
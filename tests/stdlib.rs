@@ -0,0 +1,61 @@
+mod common;
+
+use common::{purec, scratch, stdout};
+use std::fs;
+
+/// The shipped runtime files are generated from the builtin table; this
+/// fails when one of them is edited by hand or the table changes without
+/// regenerating them.
+#[test]
+fn shipped_runtime_matches_builtin_table() {
+    let dir = scratch("stdlib");
+    let root = env!("CARGO_MANIFEST_DIR");
+    for (args, file) in [
+        (&["runtime"][..], "js/std.js"),
+        (&["runtime", "--module", "esm"][..], "js/esm/std.js"),
+        (&["runtime", "--target", "vm"][..], "asm/builtins.tsv"),
+    ] {
+        let shipped = fs::read_to_string(format!("{}/{}", root, file)).unwrap();
+        assert_eq!(
+            stdout(&purec(&dir, args)),
+            shipped,
+            "{} is out of date, regenerate it with `purec {}`",
+            file,
+            args.join(" ")
+        );
+    }
+}
+
+/// Compiled VM images refer to builtins by these ids, so they must never
+/// change. New builtins are only ever appended here.
+#[test]
+fn builtin_ids_are_frozen() {
+    let frozen = [
+        (0, "std.id"),
+        (1, "std.iff"),
+        (2, "std.add"),
+        (3, "std.sub"),
+        (4, "std.mul"),
+        (5, "std.div"),
+        (6, "std.prints"),
+        (7, "std.cmd"),
+        (8, "std.mapCmd"),
+        (9, "std.swapCmd"),
+        (10, "std.thenCmd"),
+        (11, "std.chainCmd"),
+    ];
+    let dir = scratch("frozen");
+    let table = stdout(&purec(&dir, &["runtime", "--target", "vm"]));
+    let ids: Vec<(u32, &str)> = table
+        .lines()
+        .skip(2)
+        .map(|line| {
+            let mut columns = line.split('\t');
+            let id = columns.next().unwrap().parse().unwrap();
+            (id, columns.next().unwrap())
+        })
+        .collect();
+    for builtin in frozen {
+        assert!(ids.contains(&builtin), "{:?} was renumbered", builtin);
+    }
+}
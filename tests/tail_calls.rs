@@ -0,0 +1,48 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+use std::path::PathBuf;
+
+const COUNT: &str = "main.count := n -> acc ->
+  std.iff n (main.count (std.sub n 1) (std.add acc 1)) acc;
+main.main := std.prints (main.count 1000000 0);
+";
+
+const EVEN: &str = "main.even := n -> std.iff n (main.odd (std.sub n 1)) 1;
+main.odd := n -> std.iff n (main.even (std.sub n 1)) 0;
+main.main := std.prints (main.even 1000000);
+";
+
+fn build(name: &str, source: &str, flags: &[&str]) -> PathBuf {
+    let dir = scratch(name);
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(dir.join("main.pure"), source).unwrap();
+    let mut args = vec!["build", "main.pure"];
+    args.extend_from_slice(flags);
+    stdout(&purec(&dir, &args));
+    dir
+}
+
+/// A million self tail calls run in a loop, without the trampoline.
+#[test]
+fn self_tail_calls_loop() {
+    let dir = build("tail-calls", COUNT, &[]);
+    let js = fs::read_to_string(dir.join("main.js")).unwrap();
+
+    assert!(js.contains("for (;;)"), "{}", js);
+    assert_eq!(stdout(&node(&dir, "main.js")), "1000000\n");
+}
+
+/// As many mutually recursive calls overflow the stack, unless they run on
+/// the trampoline.
+#[test]
+fn trampoline_runs_deep_mutual_recursion() {
+    let plain = build("tail-calls-plain", EVEN, &[]);
+    let trampoline = build("tail-calls-trampoline", EVEN, &["--trampoline"]);
+    let overflow = node(&plain, "main.js");
+
+    assert!(String::from_utf8_lossy(&overflow.stderr)
+        .contains("Maximum call stack size exceeded"));
+    assert_eq!(stdout(&node(&trampoline, "main.js")), "1\n");
+}
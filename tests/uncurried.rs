@@ -0,0 +1,37 @@
+mod common;
+
+use common::{node, purec, scratch, stdout, STD};
+use std::fs;
+
+/// Saturated calls, also through aliases, go to the uncurried twin, while
+/// partial and curried applications still see a function of one argument.
+#[test]
+fn saturated_calls_go_through_the_twin() {
+    let dir = scratch("uncurried");
+    fs::copy(STD, dir.join("std.js")).unwrap();
+    fs::write(
+        dir.join("main.pure"),
+        "(+) := std.add;
+main.plus := (+);
+main.add3 := x -> y -> z -> x + y + z;
+main.sum3 := main.add3;
+main.apply := f -> f 4 5 6;
+main.apply2 := f -> f 1 2;
+main.main := do {
+  std.prints (main.sum3 1 2 3);
+  std.prints (main.plus 10 20);
+  std.prints (main.apply main.add3);
+  std.prints (main.apply2 (main.add3 100))
+};
+",
+    )
+    .unwrap();
+
+    stdout(&purec(&dir, &["build", "main.pure"]));
+    let js = fs::read_to_string(dir.join("main.js")).unwrap();
+
+    assert!(js.contains("function main$add3$3(x, y, z)"), "{}", js);
+    assert!(js.contains("main$add3$3(1, 2, 3)"), "{}", js);
+    assert!(js.contains("std.add$2(10, 20)"), "{}", js);
+    assert_eq!(stdout(&node(&dir, "main.js")), "6\n30\n15\n103\n");
+}